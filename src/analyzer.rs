@@ -1,3 +1,4 @@
+use std::f32::consts::PI;
 use std::sync::Arc;
 
 use rustfft::FFT;
@@ -6,15 +7,93 @@ use rustfft::num_complex::Complex;
 use rustfft::num_traits::Zero;
 
 use crate::Error;
+use crate::backend::SpectrumBackend;
+use crate::backend::RustFftBackend;
+use crate::buckets::Buckets;
+use crate::conversion;
+use crate::spectrogram;
+use crate::mel;
 use crate::sample::Sample;
 use crate::sample::SampleBuffer;
+use crate::types::Frequency;
 use crate::types::SignalStrength;
+use crate::weighting::Weighting;
 use crate::window_kind::WindowKind;
 
+/// How an analyzer treats NaN and infinite samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// Passes them through to the FFT unchanged.
+    Propagate,
+    /// Replaces them with zero.
+    Sanitize,
+    /// Fails the analysis with `Error::NonFiniteInput`.
+    Strict,
+}
+
+impl Default for NonFinitePolicy {
+    fn default() -> Self {
+        NonFinitePolicy::Propagate
+    }
+}
+
+// Reinterprets the storage of complex values as twice as many reals, without copying.
+fn complex_into_reals(complex: Vec<Complex<Sample>>) -> Vec<SignalStrength> {
+    let mut complex = std::mem::ManuallyDrop::new(complex);
+    let (ptr, len, capacity) = (complex.as_mut_ptr(), complex.len(), complex.capacity());
+
+    // `Complex` is `repr(C)` with a real and an imaginary `Sample` field, so the allocation has the same size and alignment
+    // when viewed as reals, and every one of them is initialized.
+    unsafe { Vec::from_raw_parts(ptr as *mut SignalStrength, len * 2, capacity * 2) }
+}
+
+// Reuses the storage of reals as `len` zeroed complex values, allocating instead if it is too small or cannot be split in pairs.
+fn reals_into_complex(mut reals: Vec<SignalStrength>, len: usize) -> Vec<Complex<Sample>> {
+    if reals.capacity() % 2 == 1 || reals.capacity() / 2 < len { return vec![Complex::zero(); len] }
+
+    reals.clear();
+    reals.resize(len * 2, 0.0);
+
+    let mut reals = std::mem::ManuallyDrop::new(reals);
+    let (ptr, capacity) = (reals.as_mut_ptr(), reals.capacity());
+
+    // The reverse of `complex_into_reals`, where the even capacity keeps the allocation size unchanged.
+    unsafe { Vec::from_raw_parts(ptr as *mut Complex<Sample>, len, capacity / 2) }
+}
+
+// Prepares an FFT input buffer filled with raw samples, applying the non-finite policy, the pre-emphasis filter,
+// and the window, in that order.
+fn condition_input(input: &mut [Complex<Sample>], window: &[f32], policy: NonFinitePolicy, pre_emphasis: Sample) -> Result<(), Error> {
+    let mut previous = Complex::zero();
+
+    for (i, w) in input.iter_mut().zip(window) {
+        let x = Complex::new(policy.apply(i.re)?, policy.apply(i.im)?);
+        *i = (x - previous * pre_emphasis) * w;
+        previous = x;
+    }
+
+    Ok(())
+}
+
+impl NonFinitePolicy {
+    fn apply(self, sample: Sample) -> Result<Sample, Error> {
+        if sample.is_finite() { return Ok(sample) }
+
+        match self {
+            NonFinitePolicy::Propagate => Ok(sample),
+            NonFinitePolicy::Sanitize => Ok(0.0),
+            NonFinitePolicy::Strict => Err(Error::NonFiniteInput),
+        }
+    }
+}
+
+/// Calculates power spectra of fixed-length frames, reusing its scratch buffers across calls.
+/// Clones share the planned FFT, but each gets its own buffers, so they can be used independently.
 #[derive(Clone)]
 pub struct Analyzer {
-    // Reusable FFT algorithm.
-    fft: Arc<dyn FFT<Sample>>,
+    // Reusable FFT algorithms.
+    fft: Arc<dyn SpectrumBackend>,
+    ifft: Arc<dyn FFT<Sample>>,
 
     // FFT window to use for smoothing.
     window: Vec<f32>,
@@ -26,11 +105,39 @@ pub struct Analyzer {
 
     spectrum_l: Vec<SignalStrength>,
     spectrum_r: Vec<SignalStrength>,
+
+    // Factor applied to each bin's power.
+    power_scale: SignalStrength,
+
+    non_finite_policy: NonFinitePolicy,
+
+    // Coefficient of the first-order pre-emphasis filter, where zero disables it.
+    pre_emphasis: Sample,
+
+    // Sampling rate the input is expected to have, if any.
+    sampling_rate: Option<usize>,
+
+    // RMS level below which `process` skips the FFT and outputs silence, if any.
+    silence_threshold: Option<SignalStrength>,
 }
 
 impl Analyzer {
     pub fn new(len: usize, window_kind: WindowKind) -> Self {
-        let fft = FFTplanner::new(false).plan_fft(len);
+        Self::with_backend(Arc::new(RustFftBackend::new(len)), window_kind)
+    }
+
+    /// Same as `new`, but fails instead of planning an FFT longer than `max_len`, such as for lengths read from a user's config.
+    /// The check happens before anything is allocated.
+    pub fn new_checked(len: usize, window_kind: WindowKind, max_len: usize) -> Result<Self, Error> {
+        if len > max_len { Err(Error::FftTooLarge(len, max_len))? }
+
+        Ok(Self::new(len, window_kind))
+    }
+
+    /// Creates an analyzer that uses a custom forward transform, with the FFT length taken from the backend.
+    pub fn with_backend(fft: Arc<dyn SpectrumBackend>, window_kind: WindowKind) -> Self {
+        let len = fft.len();
+        let ifft = FFTplanner::new(true).plan_fft(len);
 
         let window = window_kind.generate(len).into_iter().map(|w| w as f32).collect();
 
@@ -43,131 +150,2546 @@ impl Analyzer {
 
         Analyzer {
             fft,
+            ifft,
             window,
             input_l,
             input_r,
             output,
             spectrum_l,
             spectrum_r,
+            power_scale: 1.0,
+            non_finite_policy: NonFinitePolicy::default(),
+            pre_emphasis: 0.0,
+            sampling_rate: None,
+            silence_threshold: None,
         }
     }
 
+    /// Scales the spectrum by the sum of the window coefficients, so that the square root of a bin's power
+    /// is the amplitude of a sinusoid centered on that bin.
+    /// This corrects for the window's coherent gain, so a sinusoid has the same peak power whichever window is used.
+    /// To apply the correction by hand instead, divide by the square of `WindowKind::coherent_gain`.
+    pub fn with_amplitude_normalization(mut self, normalize: bool) -> Self {
+        self.power_scale =
+            if normalize {
+                // Halved, since a real sinusoid's energy is split between the positive and negative frequencies.
+                let half_window_sum = self.window.iter().sum::<f32>() / 2.0;
+                1.0 / half_window_sum.powi(2)
+            }
+            else { 1.0 }
+        ;
+
+        self
+    }
+
+    /// Sets how NaN and infinite samples are handled when analyzing.
+    pub fn with_non_finite_policy(mut self, policy: NonFinitePolicy) -> Self {
+        self.non_finite_policy = policy;
+        self
+    }
+
+    /// Applies the pre-emphasis filter `y[n] = x[n] - coefficient * x[n - 1]` to each frame before windowing.
+    /// This boosts high frequencies, and a coefficient of around 0.97 is typical for speech.
+    /// The first sample of a frame has no preceding sample, and is left as is.
+    pub fn with_pre_emphasis(mut self, coefficient: Sample) -> Self {
+        self.pre_emphasis = coefficient;
+        self
+    }
+
+    /// Records the sampling rate that input to this analyzer is expected to have.
+    /// The analyzer cannot detect the rate of its input, but callers can use `analyze_checked` to guard against a mismatch,
+    /// and debug builds assert that any sampling rate passed to this analyzer matches.
+    pub fn with_sampling_rate(mut self, sampling_rate: usize) -> Self {
+        self.sampling_rate = Some(sampling_rate);
+        self
+    }
+
+    /// Returns the sampling rate that input is expected to have, if one was set.
+    #[inline]
+    pub fn expects_sampling_rate(&self) -> Option<usize> {
+        self.sampling_rate
+    }
+
+    /// Makes `process` output all-zero buckets without running the FFT whenever the frame's RMS is below `threshold`,
+    /// saving work and keeping the display clean during silence.
+    pub fn with_silence_gate(mut self, threshold: SignalStrength) -> Self {
+        self.silence_threshold = Some(threshold);
+        self
+    }
+
+    /// Returns the RMS level below which `process` treats a frame as silent, if one was set.
+    #[inline]
+    pub fn silence_threshold(&self) -> Option<SignalStrength> {
+        self.silence_threshold
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.fft.len()
     }
 
+    /// Returns a copy of the window coefficients applied to each frame.
+    pub fn window_coefficients(&self) -> Vec<f32> {
+        self.window.clone()
+    }
+
+    /// Multiplies a frame by this analyzer's window coefficients in place, for use with an external FFT.
+    pub fn apply_window(&self, frame: &mut [Sample]) -> Result<(), Error> {
+        if self.len() != frame.len() { Err(Error::NumSamples(self.len(), frame.len()))? }
+
+        for (x, w) in frame.iter_mut().zip(&self.window) {
+            *x *= w;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a sample buffer holds at least a full frame for this analyzer, so that `process` never pads it with silence.
+    /// This allows an undersized buffer to be caught at setup.
+    pub fn is_compatible_with(&self, buffer: &SampleBuffer) -> bool {
+        buffer.len() >= self.len()
+    }
+
     /// Analyzes a slice of stereo audio samples.
+    /// Buffers longer than the FFT length are accepted, and only their most recent samples are used.
     pub fn analyze(&mut self, samples: &SampleBuffer) -> Result<(&[SignalStrength], &[SignalStrength]), Error> {
-        // Check to see if the number of samples is correct.
-        if self.len() != samples.len() { Err(Error::NumSamples(self.len(), samples.len()))? }
+        // Check to see if there are enough samples.
+        if self.len() > samples.len() { Err(Error::NumSamples(self.len(), samples.len()))? }
+
+        self.analyze_pairs(samples.iter_tail(self.len())?)?;
+
+        Ok((&self.spectrum_l, &self.spectrum_r))
+    }
+
+    /// Analyzes the latest frame in a sample buffer and bucketizes it, averaging the left and right spectra.
+    /// Buffers shorter than the FFT length are padded with leading silence, and only the most recent samples of longer ones are used.
+    ///
+    /// This is the intended real-time loop: an audio callback pushes each block of samples into a shared `SampleBuffer`
+    /// created with the FFT length, and the render loop calls `process` on a clone of it once per display frame.
+    /// A fresh buffer is filled with zeros, so the first few frames are quiet rather than an error.
+    pub fn process(&mut self, samples: &SampleBuffer, buckets: &Buckets, sampling_rate: usize) -> Result<Vec<SignalStrength>, Error> {
+        let len = self.len();
+        let num_samples = samples.len();
+
+        if let Some(threshold) = self.silence_threshold {
+            // Padding counts towards the frame, lowering its level.
+            let rms =
+                if num_samples < len { samples.iter().rms() * (num_samples as SignalStrength / len as SignalStrength).sqrt() }
+                else { samples.iter_tail(len)?.rms() }
+            ;
+
+            if rms < threshold { return Ok(vec![0.0; buckets.len()]) }
+        }
+
+        if num_samples < len {
+            let padding = std::iter::repeat((0.0, 0.0)).take(len - num_samples);
+            self.analyze_pairs(padding.chain(samples.iter()))?;
+        }
+        else {
+            self.analyze_pairs(samples.iter_tail(len)?)?;
+        }
 
-        for ((ref mut il, ref mut ir), ((xl, xr), w)) in self.input_l.iter_mut().zip(self.input_r.iter_mut()).zip(samples.iter().zip(&self.window)) {
-            **il = Complex::new(xl * w, 0.0);
-            **ir = Complex::new(xr * w, 0.0);
+        let mono: Vec<_> = self.spectrum_l.iter().zip(&self.spectrum_r).map(|(l, r)| (l + r) / 2.0).collect();
+
+        buckets.bucketize(&mono, sampling_rate)
+    }
+
+    // Windows and transforms one frame of sample pairs into the left and right spectra.
+    fn analyze_pairs<I>(&mut self, samples: I) -> Result<(), Error>
+    where
+        I: Iterator<Item = (Sample, Sample)>,
+    {
+        for ((il, ir), (xl, xr)) in self.input_l.iter_mut().zip(self.input_r.iter_mut()).zip(samples) {
+            *il = Complex::new(xl, 0.0);
+            *ir = Complex::new(xr, 0.0);
         }
 
-        self.fft.process(&mut self.input_l, &mut self.output);
+        condition_input(&mut self.input_l, &self.window, self.non_finite_policy, self.pre_emphasis)?;
+        condition_input(&mut self.input_r, &self.window, self.non_finite_policy, self.pre_emphasis)?;
+
+        self.fft.forward(&mut self.input_l, &mut self.output);
 
         for (s, o) in self.spectrum_l.iter_mut().zip(&self.output) {
-            *s = o.norm_sqr();
+            *s = o.norm_sqr() * self.power_scale;
         }
 
-        self.fft.process(&mut self.input_r, &mut self.output);
+        self.fft.forward(&mut self.input_r, &mut self.output);
 
         for (s, o) in self.spectrum_r.iter_mut().zip(&self.output) {
-            *s = o.norm_sqr();
+            *s = o.norm_sqr() * self.power_scale;
         }
 
-        Ok((&self.spectrum_l, &self.spectrum_r))
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Same as `analyze`, but first checks that the sampling rate claimed by the caller matches the expected one.
+    /// An analyzer without an expected sampling rate accepts any rate.
+    pub fn analyze_checked(&mut self, samples: &SampleBuffer, sampling_rate: usize) -> Result<(&[SignalStrength], &[SignalStrength]), Error> {
+        if let Some(expected) = self.sampling_rate {
+            if expected != sampling_rate { Err(Error::SampleRateMismatch(expected, sampling_rate))? }
+        }
 
-    use crate::test_util::TestUtil;
-    use crate::types::Frequency;
+        self.analyze(samples)
+    }
 
-    const SAMPLES_PER_PERIOD: usize = 44100;
-    const FREQUENCY: Frequency = 440.0;
+    /// Analyzes any number of channels of mono samples, returning the spectrum of each in order.
+    /// For stereo input, `analyze` avoids allocating and should be preferred.
+    pub fn analyze_multi(&mut self, channels: &[&[Sample]]) -> Result<Vec<Vec<SignalStrength>>, Error> {
+        for channel in channels {
+            if self.len() != channel.len() { Err(Error::NumSamples(self.len(), channel.len()))? }
+        }
 
-    #[test]
-    fn test_analyze_mono() {
-        const FFT_LEN: usize = 16;
+        let mut spectra = Vec::with_capacity(channels.len());
 
-        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+        for channel in channels {
+            self.transform_mono(channel)?;
+            spectra.push(self.output.iter().map(|o| o.norm_sqr() * self.power_scale).collect());
+        }
 
-        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+        Ok(spectra)
+    }
 
-        let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+    /// Same as `analyze_multi` for a single channel, but computes the power in the storage of the FFT output buffer
+    /// instead of allocating a separate buffer for it. `recycled` becomes the new FFT output buffer if it has room,
+    /// so passing back the spectrum from the previous call means repeated calls never allocate.
+    pub fn analyze_in_place(&mut self, samples: &[Sample], recycled: Vec<SignalStrength>) -> Result<Vec<SignalStrength>, Error> {
+        if self.len() != samples.len() { Err(Error::NumSamples(self.len(), samples.len()))? }
 
-        assert_eq!(FFT_LEN, spectrum.len());
+        self.transform_mono(samples)?;
 
-        let expected: Vec<SignalStrength> = vec![
-            3.0186355,
-            0.31955782,
-            0.07949541,
-            0.03741721,
-            0.023034703,
-            0.016638935,
-            0.013468596,
-            0.011947523,
-            0.011491794,
-            0.011947523,
-            0.013468596,
-            0.016638935,
-            0.023034703,
-            0.03741721,
-            0.07949541,
-            0.31955782,
-        ];
+        let len = self.len();
+        let output = std::mem::replace(&mut self.output, reals_into_complex(recycled, len));
 
-        for (e, ss) in expected.into_iter().zip(spectrum) {
-            assert_approx_eq!(e, ss);
+        // Each bin's power only overwrites storage of bins that were already read.
+        let mut spectrum = complex_into_reals(output);
+
+        for i in 0..len {
+            let (re, im) = (spectrum[2 * i], spectrum[2 * i + 1]);
+            spectrum[i] = (re * re + im * im) * self.power_scale;
         }
 
-        // let fft_bin_size = SAMPLE_RATE as Frequency / FFT_LEN as f32;
+        spectrum.truncate(len);
 
-        // for (n, ss) in spectrum.iter().enumerate() {
-        //     println!("{}: {} ({} Hz)", n, ss, n as f32 * fft_bin_size);
-        // }
+        Ok(spectrum)
+    }
 
-        // println!("{:?}", analyzer.buckets());
+    // Windows and transforms one frame of mono samples into the FFT output buffer.
+    fn transform_mono(&mut self, samples: &[Sample]) -> Result<(), Error> {
+        for (i, x) in self.input_l.iter_mut().zip(samples) {
+            *i = Complex::new(*x, 0.0);
+        }
+
+        condition_input(&mut self.input_l, &self.window, self.non_finite_policy, self.pre_emphasis)?;
+
+        self.fft.forward(&mut self.input_l, &mut self.output);
+
+        Ok(())
     }
 
-    #[test]
-    fn test_analyze_mono_large_window() {
-        const FFT_LEN: usize = 2048;
+    /// Analyzes a slice of mono samples, returning the single-sided spectrum from zero to the Nyquist frequency
+    /// along with the frequency of each bin, as parallel arrays for plotting.
+    pub fn spectrum_with_axis(&mut self, samples: &[Sample], sampling_rate: usize) -> Result<(Vec<Frequency>, Vec<SignalStrength>), Error> {
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
 
-        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+        let mut spectrum = self.analyze_multi(&[samples])?.remove(0);
+        spectrum.truncate(self.len() / 2 + 1);
 
-        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+        let axis = (0..spectrum.len()).map(|i| fft_bin_size * i as Frequency).collect();
 
-        let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+        Ok((axis, spectrum))
+    }
 
-        assert_eq!(FFT_LEN, spectrum.len());
+    /// Calculates a high-resolution spectrum of the band `span` hertz wide around `center`, using a zoom FFT.
+    /// The samples are shifted down so that `center` is at zero, then averaged in blocks to decimate them to a sampling rate
+    /// of at least `span`, before taking a complex FFT of the result. Returns the frequency and power of each bin, in increasing order.
+    /// This needs `len` times the decimation factor of samples, and the most recent ones are used.
+    pub fn zoom_spectrum(&mut self, samples: &[Sample], sampling_rate: usize, center: Frequency, span: Frequency) -> Result<Vec<(Frequency, SignalStrength)>, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+        if !(span > 0.0) { Err(Error::ZoomSpan)? }
 
-        let fft_bin_size = SAMPLES_PER_PERIOD as Frequency / FFT_LEN as f32;
+        let len = self.len();
+        let decimation = ((sampling_rate as Frequency / span).floor() as usize).max(1);
 
-        let expected_max_spectrum_index = (FREQUENCY / fft_bin_size) as usize;
+        let needed = len * decimation;
+        if samples.len() < needed { Err(Error::TooFewSamples(needed, samples.len()))? }
 
-        let produced_max_spectrum_index =
-            spectrum
-            .iter()
-            .take(FFT_LEN / 2)
-            .enumerate()
-            .max_by(|(_, sa), (_, sb)| sa.partial_cmp(&sb).unwrap())
-            .map(|(i, _)| i)
-            .unwrap()
+        let frame = &samples[samples.len() - needed..];
+
+        // The phase is tracked in double precision, since frames can be very long.
+        let step = -2.0 * std::f64::consts::PI * center as f64 / sampling_rate as f64;
+
+        let decimated: Vec<_> =
+            frame.chunks_exact(decimation).enumerate()
+            .map(|(block, chunk)| {
+                let sum: Complex<Sample> =
+                    chunk.iter().enumerate()
+                    .map(|(i, &x)| {
+                        let phase = step * (block * decimation + i) as f64;
+                        Complex::new(x * phase.cos() as Sample, x * phase.sin() as Sample)
+                    })
+                    .sum()
+                ;
+
+                sum / decimation as Sample
+            })
+            .collect()
         ;
 
-        println!("{}, {}", expected_max_spectrum_index, produced_max_spectrum_index);
-        assert_eq!(expected_max_spectrum_index, produced_max_spectrum_index);
+        let zoomed_bin_size = sampling_rate as Frequency / decimation as Frequency / len as Frequency;
+        let spectrum = self.analyze_complex(&decimated)?;
 
-        // for (n, ss) in spectrum.iter().enumerate() {
-        //     println!("{}: {} ({} Hz)", n, ss, n as f32 * fft_bin_size);
-        // }
+        // Bins above the midpoint are below the center frequency.
+        let num_positive = (len + 1) / 2;
+
+        let zoomed =
+            (0..len)
+            .map(|k| (k + num_positive) % len)
+            .map(|k| {
+                let offset = if k < num_positive { k as Frequency } else { k as Frequency - len as Frequency };
+                (center + offset * zoomed_bin_size, spectrum[k])
+            })
+            .collect()
+        ;
+
+        Ok(zoomed)
+    }
+
+    /// Analyzes a unit impulse at the center of the frame, for self-testing the configured analysis chain.
+    /// With no pre-emphasis, the result is flat, at the square of the window's center coefficient times the power scale.
+    pub fn impulse_response_spectrum(&mut self) -> Result<Vec<SignalStrength>, Error> {
+        let mut impulse = vec![0.0; self.len()];
+        if let Some(center) = impulse.get_mut(self.len() / 2) { *center = 1.0; }
+
+        Ok(self.analyze_multi(&[&impulse])?.remove(0))
+    }
+
+    /// Analyzes a slice of complex (I/Q) samples, returning the power of all bins.
+    /// Unlike with real input, the spectrum is not symmetric:
+    /// bins above the midpoint represent negative frequencies, with the last bin being the lowest negative frequency.
+    pub fn analyze_complex(&mut self, samples: &[Complex<Sample>]) -> Result<&[SignalStrength], Error> {
+        if self.len() != samples.len() { Err(Error::NumSamples(self.len(), samples.len()))? }
+
+        self.input_l.copy_from_slice(samples);
+
+        condition_input(&mut self.input_l, &self.window, self.non_finite_policy, self.pre_emphasis)?;
+
+        self.fft.forward(&mut self.input_l, &mut self.output);
+
+        for (s, o) in self.spectrum_l.iter_mut().zip(&self.output) {
+            *s = o.norm_sqr() * self.power_scale;
+        }
+
+        Ok(&self.spectrum_l)
+    }
+
+    /// Calculates the cross-spectrum `conj(X) * Y` of two windowed channels.
+    pub fn cross_spectrum(&mut self, x: &[Sample], y: &[Sample]) -> Result<Vec<Complex<Sample>>, Error> {
+        self.transform_both(x, y)?;
+
+        Ok(self.input_l.iter().zip(&self.output).map(|(ox, oy)| ox.conj() * (oy * self.power_scale)).collect())
+    }
+
+    // Adds the cross-spectrum of two channels and the power spectrum of each to running sums,
+    // reusing the same two transforms for all three.
+    pub(crate) fn accumulate_cross_spectra(&mut self, x: &[Sample], y: &[Sample], sum_xy: &mut [Complex<Sample>], sum_xx: &mut [SignalStrength], sum_yy: &mut [SignalStrength]) -> Result<(), Error> {
+        self.transform_both(x, y)?;
+
+        let sums = sum_xy.iter_mut().zip(sum_xx.iter_mut()).zip(sum_yy.iter_mut());
+
+        for (((sxy, sxx), syy), (ox, oy)) in sums.zip(self.input_l.iter().zip(&self.output)) {
+            *sxy += ox.conj() * (oy * self.power_scale);
+            *sxx += ox.norm_sqr() * self.power_scale;
+            *syy += oy.norm_sqr() * self.power_scale;
+        }
+
+        Ok(())
+    }
+
+    // Windows and transforms two channels, leaving the spectrum of `x` in the left input buffer and that of `y` in the output buffer.
+    fn transform_both(&mut self, x: &[Sample], y: &[Sample]) -> Result<(), Error> {
+        if self.len() != x.len() { Err(Error::NumSamples(self.len(), x.len()))? }
+        if self.len() != y.len() { Err(Error::NumSamples(self.len(), y.len()))? }
+
+        for ((ix, iy), (sx, sy)) in self.input_l.iter_mut().zip(self.input_r.iter_mut()).zip(x.iter().zip(y)) {
+            *ix = Complex::new(*sx, 0.0);
+            *iy = Complex::new(*sy, 0.0);
+        }
+
+        condition_input(&mut self.input_l, &self.window, self.non_finite_policy, self.pre_emphasis)?;
+        condition_input(&mut self.input_r, &self.window, self.non_finite_policy, self.pre_emphasis)?;
+
+        self.fft.forward(&mut self.input_l, &mut self.output);
+        self.input_l.copy_from_slice(&self.output);
+
+        self.fft.forward(&mut self.input_r, &mut self.output);
+
+        Ok(())
+    }
+
+    /// Calculates the crest factor of the most recent frame's worth of samples in a buffer.
+    pub fn time_crest_factor(&self, samples: &SampleBuffer) -> Result<f32, Error> {
+        Ok(samples.iter_tail(self.len())?.crest_factor())
+    }
+
+    /// Calculates the number of sign changes per sample over the most recent frame's worth of samples.
+    /// This divides by the frame length rather than the number of consecutive pairs, so a signal alternating every sample
+    /// reads `(n - 1) / n` rather than one. Zero is counted as positive.
+    pub fn zero_crossing_rate(&self, samples: &[Sample]) -> Result<f32, Error> {
+        if samples.len() < self.len() { Err(Error::TooFewSamples(self.len(), samples.len()))? }
+
+        let frame = &samples[samples.len() - self.len()..];
+
+        if frame.is_empty() { return Ok(0.0) }
+
+        let crossings = frame.windows(2).filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0)).count();
+
+        Ok(crossings as f32 / frame.len() as f32)
+    }
+
+    /// Calculates the width of a single FFT bin in hertz.
+    pub fn fft_bin_size(&self, sampling_rate: usize) -> Result<Frequency, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+        // Every method that works in hertz goes through here.
+        debug_assert!(
+            self.sampling_rate.unwrap_or(sampling_rate) == sampling_rate,
+            "sampling rate does not match the expected rate",
+        );
+
+        Ok(sampling_rate as Frequency / self.len() as Frequency)
+    }
+
+    /// Calculates the effective width of each bin in hertz, which is the bin size widened by the window's
+    /// equivalent noise bandwidth. This is closer to the analyzer's real ability to separate two tones.
+    pub fn effective_resolution(&self, sampling_rate: usize) -> Result<Frequency, Error> {
+        let sum: f32 = self.window.iter().sum();
+        let sum_squares: f32 = self.window.iter().map(|w| w * w).sum();
+
+        // Same as `WindowKind::enbw`, for the coefficients in use.
+        let enbw = if sum > 0.0 { self.len() as f32 * sum_squares / sum.powi(2) } else { 1.0 };
+
+        Ok(self.fft_bin_size(sampling_rate)? * enbw)
+    }
+
+    /// Calculates the frequency of the FFT bin at an index.
+    pub fn bin_frequency(&self, index: usize, sampling_rate: usize) -> Result<Frequency, Error> {
+        if !(index < self.len()) { Err(Error::BinIndex(index, self.len()))? }
+
+        Ok(self.fft_bin_size(sampling_rate)? * index as Frequency)
+    }
+
+    /// Returns the index and frequency of the highest valid FFT bin.
+    /// For even FFT lengths, this is the Nyquist frequency.
+    pub fn nyquist_bin(&self, sampling_rate: usize) -> Result<(usize, Frequency), Error> {
+        let index = self.len() / 2;
+        Ok((index, self.bin_frequency(index, sampling_rate)?))
+    }
+
+    /// Finds the frequency of the loudest FFT bin, along with the fraction of the total power in that bin.
+    /// The frequency is refined to sub-bin accuracy by fitting a parabola through the log power of the peak and its neighbors.
+    /// A silent spectrum has no dominant frequency.
+    pub fn dominant_frequency(&self, spectrum: &[SignalStrength], sampling_rate: usize) -> Result<Option<(Frequency, f32)>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        let last = spectrum.len() / 2;
+        let valid_fft_indices = 1..=last;
+
+        let total: SignalStrength = spectrum[valid_fft_indices.clone()].iter().sum();
+
+        if !(total > 0.0) { return Ok(None) }
+
+        let dominant =
+            valid_fft_indices
+            .max_by(|&a, &b| spectrum[a].total_cmp(&spectrum[b]))
+            .map(|i| {
+                // Peaks at the edges of the valid range, or next to silent bins, are left as is.
+                let offset =
+                    if i > 1 && i < last && spectrum[i - 1] > 0.0 && spectrum[i + 1] > 0.0 {
+                        let (a, b, c) = (spectrum[i - 1].ln(), spectrum[i].ln(), spectrum[i + 1].ln());
+                        let denominator = a - 2.0 * b + c;
+
+                        if denominator < 0.0 { (0.5 * (a - c) / denominator).clamp(-0.5, 0.5) }
+                        else { 0.0 }
+                    }
+                    else { 0.0 }
+                ;
+
+                (fft_bin_size * (i as Frequency + offset), spectrum[i] / total)
+            })
+        ;
+
+        Ok(dominant)
+    }
+
+    /// Calculates the spectral contrast in decibels of each of `sub_bands` logarithmically spaced groups of bins.
+    /// The contrast is the ratio of the mean power of the loudest fifth of a group's bins to that of the quietest fifth.
+    /// Groups too narrow to contain any bins have a contrast of zero.
+    pub fn spectral_contrast(&self, spectrum: &[SignalStrength], sub_bands: usize) -> Result<Vec<f32>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+        if !(sub_bands > 0) { Err(Error::NumBands)? }
+
+        // Keeps silent valleys from producing an infinite contrast.
+        const EPSILON: SignalStrength = 1e-10;
+
+        let num_valid_bins = spectrum.len() / 2;
+        let edge = |b: usize| -> usize {
+            let e = (num_valid_bins as f32 + 1.0).powf(b as f32 / sub_bands as f32).round() as usize;
+            e.max(1).min(num_valid_bins + 1)
+        };
+
+        let mut contrast = Vec::with_capacity(sub_bands);
+
+        for b in 0..sub_bands {
+            let mut powers = spectrum[edge(b)..edge(b + 1)].to_vec();
+
+            if powers.is_empty() {
+                contrast.push(0.0);
+                continue;
+            }
+
+            powers.sort_by(|a, b| a.total_cmp(b));
+
+            let n = (powers.len() / 5).max(1);
+            let valley = powers[..n].iter().sum::<SignalStrength>() / n as SignalStrength;
+            let peak = powers[powers.len() - n..].iter().sum::<SignalStrength>() / n as SignalStrength;
+
+            contrast.push(10.0 * ((peak + EPSILON) / (valley + EPSILON)).log10());
+        }
+
+        Ok(contrast)
+    }
+
+    /// Transforms a complex spectrum back into the time domain, scaled by `1 / len` so that a forward transform
+    /// followed by this one returns the original samples.
+    /// This uses the positive exponent `e^(+2πikn/N)` directly, so the spectrum should not be conjugated beforehand.
+    /// Any window applied before the forward transform is not undone.
+    pub fn inverse_fft(&self, spectrum: &[Complex<Sample>]) -> Result<Vec<Complex<Sample>>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let mut input = spectrum.to_vec();
+        let mut output = vec![Complex::zero(); self.len()];
+
+        self.ifft.process(&mut input, &mut output);
+
+        let scale = 1.0 / self.len() as Sample;
+        for o in output.iter_mut() {
+            *o *= scale;
+        }
+
+        Ok(output)
+    }
+
+    /// Detects the fundamental frequency of a spectrum from the peak of its real cepstrum,
+    /// looking for pitches between 50 Hz and 1 kHz.
+    /// Returns `None` if there is no cepstral peak in that range.
+    pub fn detect_pitch_cepstrum(&self, spectrum: &[SignalStrength], sampling_rate: usize) -> Result<Option<Frequency>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+        const MIN_PITCH: Frequency = 50.0;
+        const MAX_PITCH: Frequency = 1000.0;
+
+        // Keeps silent bins from producing an infinite log magnitude.
+        const EPSILON: SignalStrength = 1e-10;
+
+        // The log magnitude is half the log power.
+        let log_magnitude: Vec<_> = spectrum.iter().map(|p| Complex::new(0.5 * (p + EPSILON).ln(), 0.0)).collect();
+        let cepstrum = self.inverse_fft(&log_magnitude)?;
+
+        // A quefrency of `q` samples corresponds to a pitch of `sampling_rate / q`.
+        let min_quefrency = ((sampling_rate as Frequency / MAX_PITCH).ceil() as usize).max(1);
+        let max_quefrency = ((sampling_rate as Frequency / MIN_PITCH).floor() as usize).min(self.len() / 2);
+
+        let peak =
+            (min_quefrency..=max_quefrency)
+            .max_by(|&a, &b| cepstrum[a].re.total_cmp(&cepstrum[b].re))
+            .filter(|&q| cepstrum[q].re > 0.0)
+            .map(|q| sampling_rate as Frequency / q as Frequency)
+        ;
+
+        Ok(peak)
+    }
+
+    /// Calculates the power-weighted mean frequency of a spectrum.
+    /// A silent spectrum has a centroid of zero.
+    pub fn spectral_centroid(&self, spectrum: &[SignalStrength], sampling_rate: usize) -> Result<Frequency, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        // Same valid range as used for bucketizing, skipping the zero frequency.
+        let (weighted_sum, total) =
+            (1..=(spectrum.len() / 2))
+            .map(|i| (fft_bin_size * i as Frequency, spectrum[i]))
+            .fold((0.0, 0.0), |(ws, t), (f, p)| (ws + f * p, t + p))
+        ;
+
+        if total > 0.0 { Ok(weighted_sum / total) }
+        else { Ok(0.0) }
+    }
+
+    /// Maps the spectral centroid onto a logarithmic scale from zero at `lower_cutoff` to one at `upper_cutoff`,
+    /// for use as a single brightness value. Centroids outside of the cutoffs are clamped, and silence has a brightness of zero.
+    pub fn brightness(&self, spectrum: &[SignalStrength], sampling_rate: usize, lower_cutoff: Frequency, upper_cutoff: Frequency) -> Result<f32, Error> {
+        if !(upper_cutoff > 0.0) { Err(Error::UpperCutoff)? }
+        if !(lower_cutoff > 0.0) { Err(Error::LowerCutoff)? }
+        if !(lower_cutoff < upper_cutoff) { Err(Error::CutoffOrder)? }
+
+        let centroid = self.spectral_centroid(spectrum, sampling_rate)?;
+
+        if !(centroid > 0.0) { return Ok(0.0) }
+
+        Ok(((centroid / lower_cutoff).ln() / (upper_cutoff / lower_cutoff).ln()).clamp(0.0, 1.0))
+    }
+
+    /// Sums the power of the bins below `cutoff`, including the zero frequency,
+    /// to show how much DC offset and subsonic content the input has.
+    pub fn subsonic_energy(&self, spectrum: &[SignalStrength], sampling_rate: usize, cutoff: Frequency) -> Result<SignalStrength, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        let energy =
+            (0..=(spectrum.len() / 2))
+            .take_while(|&i| fft_bin_size * (i as Frequency) < cutoff)
+            .map(|i| spectrum[i])
+            .sum()
+        ;
+
+        Ok(energy)
+    }
+
+    /// Calculates the spectral flux of each full frame of mono samples, with frames starting `hop` samples apart.
+    /// The flux is the summed increase in power of each valid bin since the previous frame, with the first frame compared against silence.
+    pub fn onset_function(&mut self, samples: &[Sample], hop: usize) -> Result<Vec<f32>, Error> {
+        let fft_len = self.len();
+        let num_frames = spectrogram::frame_count(samples.len(), fft_len, hop)?;
+
+        let mut previous = vec![0.0; fft_len];
+        let mut flux = Vec::with_capacity(num_frames);
+
+        for i in 0..num_frames {
+            let start = i * hop;
+            let frame = SampleBuffer::from(samples[start..start + fft_len].to_vec());
+
+            let (spectrum, _) = self.analyze(&frame)?;
+
+            let frame_flux =
+                (1..=(fft_len / 2))
+                .map(|b| (spectrum[b] - previous[b]).max(0.0))
+                .sum()
+            ;
+
+            previous.copy_from_slice(spectrum);
+            flux.push(frame_flux);
+        }
+
+        Ok(flux)
+    }
+
+    /// Subtracts the power that each peak in bucketized output is expected to have leaked into its neighboring buckets,
+    /// based on the response of this analyzer's window. A peak is a bucket louder than both of its neighbors,
+    /// and its power is assumed to come from a tone at the bucket's geometric center.
+    /// Only the window response within 16 bins of the tone is considered, and buckets are never reduced below zero.
+    pub fn deleak_buckets(&self, layout: &Buckets, sampling_rate: usize, buckets: &mut [SignalStrength]) -> Result<(), Error> {
+        if layout.len() != buckets.len() { Err(Error::BucketsLength(layout.len(), buckets.len()))? }
+
+        const SPAN: f32 = 16.0;
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+        let (nyquist_index, _) = self.nyquist_bin(sampling_rate)?;
+
+        let window_sum: f32 = self.window.iter().sum();
+        if !(window_sum > 0.0) { return Ok(()) }
+
+        let n = self.len() as f32;
+
+        // Power response of the window at an offset in bins from a tone, relative to the tone's own bin.
+        let response = |offset: f32| {
+            let (re, im) =
+                self.window.iter().enumerate()
+                .fold((0.0f32, 0.0f32), |(re, im), (k, w)| {
+                    let phase = -2.0 * PI * offset * k as f32 / n;
+                    (re + w * phase.cos(), im + w * phase.sin())
+                })
+            ;
+
+            (re.powi(2) + im.powi(2)) / window_sum.powi(2)
+        };
+
+        // Total response over the bins of a band, out of the responses tabulated for a tone.
+        let band_response = |responses: &[(usize, f32)], (lo, hi): (Frequency, Frequency)| {
+            responses.iter()
+            .filter(|(i, _)| (lo..hi).contains(&(fft_bin_size * *i as Frequency)))
+            .map(|(_, r)| r)
+            .sum::<f32>()
+        };
+
+        let original = buckets.to_vec();
+        let bands = layout.bands();
+
+        // Response at each valid bin near the current peak's tone, so that each is only calculated once per peak.
+        let mut responses = Vec::new();
+
+        for (i, &peak) in original.iter().enumerate() {
+            let neighbors = [i.checked_sub(1), Some(i + 1).filter(|j| *j < original.len())];
+
+            if !neighbors.iter().flatten().all(|j| peak > original[*j]) { continue }
+
+            let (lo, hi) = bands[i];
+            let tone = (lo * hi).sqrt() / fft_bin_size;
+
+            let first = ((tone - SPAN).floor().max(1.0)) as usize;
+            let last = ((tone + SPAN).ceil() as usize).min(nyquist_index);
+
+            responses.clear();
+            responses.extend((first..=last).map(|k| (k, response(k as f32 - tone))));
+
+            let own_response = band_response(&responses, bands[i]);
+            if !(own_response > 0.0) { continue }
+
+            for j in neighbors.iter().flatten() {
+                let leakage = peak * band_response(&responses, bands[*j]) / own_response;
+                buckets[*j] = (buckets[*j] - leakage).max(0.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calculates the sound pressure level in each ISO third-octave band, along with the band's center frequency.
+    /// The calibration offset is the SPL in dB of a spectrum power of one, as measured for the input chain in use.
+    /// Bands centered above the Nyquist frequency are omitted, and bands without any energy read 120 dB below the offset.
+    pub fn third_octave_spl(&self, spectrum: &[SignalStrength], sampling_rate: usize, calibration_offset_db: f32) -> Result<Vec<(Frequency, f32)>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        const FLOOR_DB: f32 = -120.0;
+
+        let (_, nyquist) = self.nyquist_bin(sampling_rate)?;
+
+        let buckets = Buckets::new_third_octave();
+        let levels = conversion::power_to_db(&buckets.bucketize(spectrum, sampling_rate)?, FLOOR_DB);
+
+        let spl =
+            buckets.bands().iter().zip(levels)
+            .map(|((lo, hi), level)| ((lo * hi).sqrt(), level + calibration_offset_db))
+            .filter(|(center, _)| *center < nyquist)
+            .collect()
+        ;
+
+        Ok(spl)
+    }
+
+    /// Calculates the share of total energy in each octave band, along with the band's center frequency.
+    /// Bands are centered on octaves of 1 kHz from 31.25 Hz to 16 kHz, and any above the Nyquist frequency are omitted.
+    /// The shares sum to one, unless the bands contain no energy at all.
+    pub fn energy_per_octave(&self, spectrum: &[SignalStrength], sampling_rate: usize) -> Result<Vec<(Frequency, f32)>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let mut octaves: Vec<(Frequency, SignalStrength)> =
+            self.octave_bins(sampling_rate)?
+            .into_iter()
+            .map(|(center, bins)| (center, bins.map(|i| spectrum[i]).sum()))
+            .collect()
+        ;
+
+        let total: SignalStrength = octaves.iter().map(|(_, e)| e).sum();
+
+        if total > 0.0 {
+            for (_, energy) in octaves.iter_mut() {
+                *energy /= total;
+            }
+        }
+
+        Ok(octaves)
+    }
+
+    /// Calculates the crest factor of the power within each octave band, along with the band's center frequency.
+    /// This is the ratio of the loudest bin's power to the mean power of the band, using the same bands as `energy_per_octave`.
+    /// Bands that are silent or contain no bins have a crest factor of zero.
+    pub fn crest_per_octave(&self, spectrum: &[SignalStrength], sampling_rate: usize) -> Result<Vec<(Frequency, f32)>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let crests =
+            self.octave_bins(sampling_rate)?
+            .into_iter()
+            .map(|(center, bins)| {
+                let num_bins = bins.len();
+                let (peak, total) = bins.fold((0.0f32, 0.0), |(p, t), i| (p.max(spectrum[i]), t + spectrum[i]));
+
+                let crest = if total > 0.0 { peak / (total / num_bins as SignalStrength) } else { 0.0 };
+                (center, crest)
+            })
+            .collect()
+        ;
+
+        Ok(crests)
+    }
+
+    // Finds the range of valid bins within each octave band centered on an octave of 1 kHz from 31.25 Hz to 16 kHz,
+    // omitting any band that starts above the Nyquist frequency.
+    fn octave_bins(&self, sampling_rate: usize) -> Result<Vec<(Frequency, std::ops::Range<usize>)>, Error> {
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+        let (nyquist_index, nyquist) = self.nyquist_bin(sampling_rate)?;
+
+        const SQRT_2: Frequency = std::f32::consts::SQRT_2;
+
+        // Index of the first valid bin at or above a frequency.
+        let first_bin_from = |frequency: Frequency| ((frequency / fft_bin_size).ceil() as usize).clamp(1, nyquist_index + 1);
+
+        let octaves =
+            (-5..=4)
+            .map(|k| 1000.0 * (2.0 as Frequency).powi(k))
+            .filter(|center| center / SQRT_2 < nyquist)
+            .map(|center| (center, first_bin_from(center / SQRT_2)..first_bin_from(center * SQRT_2)))
+            .collect()
+        ;
+
+        Ok(octaves)
+    }
+
+    /// Finds the local maxima of a spectrum that stand more than `margin_db` above the local noise floor.
+    /// The floor around each bin is the median power of the valid bins within 16 bins of it.
+    pub fn significant_peaks(&self, spectrum: &[SignalStrength], sampling_rate: usize, margin_db: f32) -> Result<Vec<(Frequency, SignalStrength)>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        const HALF_WIDTH: usize = 16;
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        let first = 1;
+        let last = spectrum.len() / 2;
+
+        let margin = 10.0f32.powf(margin_db / 10.0);
+
+        let mut neighborhood = Vec::with_capacity(2 * HALF_WIDTH + 1);
+        let mut peaks = Vec::new();
+
+        for i in first..=last {
+            let power = spectrum[i];
+
+            let is_local_max =
+                power > 0.0
+                && (i == first || power > spectrum[i - 1])
+                && (i == last || power >= spectrum[i + 1])
+            ;
+
+            if !is_local_max { continue }
+
+            neighborhood.clear();
+            neighborhood.extend_from_slice(&spectrum[i.saturating_sub(HALF_WIDTH).max(first)..=(i + HALF_WIDTH).min(last)]);
+            neighborhood.sort_by(|a, b| a.total_cmp(b));
+
+            let floor = neighborhood[neighborhood.len() / 2];
+
+            if power > floor * margin { peaks.push((fft_bin_size * i as Frequency, power)); }
+        }
+
+        Ok(peaks)
+    }
+
+    /// Calculates the total harmonic distortion of a tone with a known fundamental, as a percentage.
+    /// This is the ratio of the combined amplitude of the next `num_harmonics` harmonics to that of the fundamental.
+    /// Each tone's power is summed over the few bins around it, to include the spread of the window.
+    /// Harmonics above the Nyquist frequency are left out, and a spectrum without any fundamental has no distortion.
+    pub fn thd(&self, spectrum: &[SignalStrength], sampling_rate: usize, fundamental: Frequency, num_harmonics: usize) -> Result<f32, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        const HALF_WIDTH: usize = 3;
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+        let (nyquist_index, nyquist) = self.nyquist_bin(sampling_rate)?;
+
+        if !(fundamental > 0.0 && fundamental < nyquist) { Err(Error::Fundamental)? }
+
+        let tone_power = |frequency: Frequency| -> SignalStrength {
+            let center = (frequency / fft_bin_size).round() as usize;
+            let first = center.saturating_sub(HALF_WIDTH).max(1);
+            let last = (center + HALF_WIDTH).min(nyquist_index);
+            spectrum[first..=last].iter().sum()
+        };
+
+        let fundamental_power = tone_power(fundamental);
+
+        if !(fundamental_power > 0.0) { return Ok(0.0) }
+
+        let harmonic_power: SignalStrength =
+            (2..=(num_harmonics + 1))
+            .map(|h| fundamental * h as Frequency)
+            .take_while(|&f| f < nyquist)
+            .map(tone_power)
+            .sum()
+        ;
+
+        Ok(100.0 * (harmonic_power / fundamental_power).sqrt())
+    }
+
+    /// Calculates the RMS of the bin magnitudes over the valid bins, which is the square root of their mean power.
+    pub fn spectrum_rms(&self, spectrum: &[SignalStrength]) -> Result<SignalStrength, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let valid = &spectrum[1..=(spectrum.len() / 2)];
+
+        if valid.is_empty() { return Ok(0.0) }
+
+        let mean_power = valid.iter().sum::<SignalStrength>() / valid.len() as SignalStrength;
+
+        Ok(mean_power.sqrt())
+    }
+
+    /// Calculates log-mel features, the natural log of the energy in each of `n_mels` triangular mel filters.
+    /// The filters span from zero to Nyquist, and a small offset keeps silent filters finite.
+    pub fn log_mel(&self, spectrum: &[SignalStrength], sampling_rate: usize, n_mels: usize) -> Result<Vec<f32>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+        if !(n_mels > 0) { Err(Error::NumBands)? }
+
+        const EPSILON: SignalStrength = 1e-10;
+
+        let log_mel =
+            mel::filterbank_energies(spectrum, sampling_rate, n_mels)
+            .into_iter()
+            .map(|e| (e + EPSILON).ln())
+            .collect()
+        ;
+
+        Ok(log_mel)
+    }
+
+    /// Calculates the first `n_coeffs` mel-frequency cepstral coefficients, the DCT-II of the log-mel features.
+    /// The zeroth coefficient is the sum of the log-mel features, and so follows the overall energy.
+    pub fn mfcc(&self, spectrum: &[SignalStrength], sampling_rate: usize, n_mels: usize, n_coeffs: usize) -> Result<Vec<f32>, Error> {
+        let log_mel = self.log_mel(spectrum, sampling_rate, n_mels)?;
+
+        if !(n_coeffs > 0 && n_coeffs <= n_mels) { Err(Error::NumCoefficients(n_coeffs, n_mels))? }
+
+        let coefficients =
+            (0..n_coeffs)
+            .map(|k| {
+                log_mel.iter().enumerate()
+                .map(|(m, x)| x * (PI * k as f32 * (m as f32 + 0.5) / n_mels as f32).cos())
+                .sum()
+            })
+            .collect()
+        ;
+
+        Ok(coefficients)
+    }
+
+    /// Estimates the noise floor of a spectrum as the median power of its valid bins.
+    /// Narrow peaks only occupy a few bins, so they barely affect the estimate.
+    pub fn noise_floor(&self, spectrum: &[SignalStrength]) -> Result<SignalStrength, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let mut valid = spectrum[1..=(spectrum.len() / 2)].to_vec();
+
+        if valid.is_empty() { return Ok(0.0) }
+
+        valid.sort_by(|a, b| a.total_cmp(b));
+
+        // For an even number of bins, this averages the two middle values.
+        let lower = valid[(valid.len() - 1) / 2];
+        let upper = valid[valid.len() / 2];
+
+        Ok((lower + upper) / 2.0)
+    }
+
+    /// Flattens a spectrum by removing the linear tilt of its decibel level against log frequency,
+    /// such as the natural high-frequency rolloff of music.
+    /// The line is fitted to the non-silent valid bins, and the mean level is kept, so only the slope is removed.
+    /// The zero-frequency bin has no log frequency, and is left as is.
+    pub fn detilt(&self, spectrum: &[SignalStrength]) -> Result<Vec<SignalStrength>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let points: Vec<(f32, f32)> =
+            (1..=(spectrum.len() / 2))
+            .filter(|&i| spectrum[i] > 0.0)
+            .map(|i| ((i as f32).ln(), 10.0 * spectrum[i].log10()))
+            .collect()
+        ;
+
+        let mut detilted = spectrum.to_vec();
+
+        if points.len() < 2 { return Ok(detilted) }
+
+        let n = points.len() as f32;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f32>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f32>() / n;
+
+        let covariance: f32 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f32 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+        let slope = covariance / variance;
+
+        for (i, d) in detilted.iter_mut().enumerate().skip(1) {
+            // Bins above the midpoint mirror those below it.
+            let bin = i.min(spectrum.len() - i);
+            let tilt_db = slope * ((bin as f32).ln() - mean_x);
+
+            *d *= 10.0f32.powf(-tilt_db / 10.0);
+        }
+
+        Ok(detilted)
+    }
+
+    /// Analyzes a frame of mono samples, returning its spectrum with a weighting curve applied to each bin.
+    /// Bins above the Nyquist frequency are weighted by the frequency they mirror, and the zero frequency by that of DC.
+    /// Bucketizing the result gives more accurate weighted bands than weighting the buckets afterwards.
+    pub fn calculate_weighted_spectrum(&mut self, samples: &[Sample], sampling_rate: usize, weighting: Weighting) -> Result<Vec<SignalStrength>, Error> {
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        let mut spectrum = self.analyze_multi(&[samples])?.remove(0);
+        let n = spectrum.len();
+
+        for (i, s) in spectrum.iter_mut().enumerate() {
+            *s *= weighting.power_gain(fft_bin_size * i.min(n - i) as Frequency);
+        }
+
+        Ok(spectrum)
+    }
+
+    /// Analyzes a frame of mono samples, returning how far A-weighting moves its level, in decibels.
+    /// This is the A-weighted level minus the flat level, so it is negative when the weighting reduces the reading,
+    /// as with bass-heavy material. Silence has a difference of zero.
+    pub fn weighting_delta(&mut self, samples: &[Sample], sampling_rate: usize) -> Result<f32, Error> {
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        let spectrum = self.analyze_multi(&[samples])?.remove(0);
+
+        let (flat, weighted) =
+            (1..=(spectrum.len() / 2))
+            .map(|i| (spectrum[i], spectrum[i] * Weighting::A.power_gain(fft_bin_size * i as Frequency)))
+            .fold((0.0, 0.0), |(f, w), (p, pw)| (f + p, w + pw))
+        ;
+
+        if !(flat > 0.0) { return Ok(0.0) }
+
+        Ok(10.0 * (weighted / flat).log10())
+    }
+
+    /// Resamples a spectrum at `points` frequencies spaced geometrically from `lower_cutoff` to `upper_cutoff` inclusive.
+    /// The power at each frequency is interpolated linearly between the two nearest bins, for drawing smooth log-frequency plots.
+    /// Frequencies above the Nyquist frequency take the power of the last valid bin.
+    pub fn log_resample(&self, spectrum: &[SignalStrength], sampling_rate: usize, lower_cutoff: Frequency, upper_cutoff: Frequency, points: usize) -> Result<Vec<(Frequency, SignalStrength)>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        if !(upper_cutoff > 0.0) { Err(Error::UpperCutoff)? }
+        if !(lower_cutoff > 0.0) { Err(Error::LowerCutoff)? }
+        if !(lower_cutoff < upper_cutoff) { Err(Error::CutoffOrder)? }
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        let ratio = (upper_cutoff / lower_cutoff).powf(1.0 / (points.max(2) - 1) as f32);
+
+        let resampled =
+            (0..points)
+            .map(|k| {
+                let frequency = lower_cutoff * ratio.powi(k as i32);
+                (frequency, interpolate_bins(spectrum, fft_bin_size, frequency))
+            })
+            .collect()
+        ;
+
+        Ok(resampled)
+    }
+
+    /// Resamples a spectrum onto an arbitrary grid of frequencies, so that spectra taken at different sampling rates
+    /// or FFT lengths can be compared point by point.
+    /// The power at each frequency is interpolated linearly between the two nearest bins,
+    /// and frequencies above the Nyquist frequency take the power of the last valid bin.
+    pub fn resample_to_grid(&self, spectrum: &[SignalStrength], sampling_rate: usize, grid: &[Frequency]) -> Result<Vec<SignalStrength>, Error> {
+        if self.len() != spectrum.len() { Err(Error::SpectrumLength(self.len(), spectrum.len()))? }
+
+        let fft_bin_size = self.fft_bin_size(sampling_rate)?;
+
+        Ok(grid.iter().map(|&frequency| interpolate_bins(spectrum, fft_bin_size, frequency)).collect())
+    }
+}
+
+// Linearly interpolates the power of a spectrum at a frequency, limited to the valid bins.
+fn interpolate_bins(spectrum: &[SignalStrength], fft_bin_size: Frequency, frequency: Frequency) -> SignalStrength {
+    let last = spectrum.len() / 2;
+    let position = (frequency / fft_bin_size).clamp(0.0, last as f32);
+
+    let i = (position.floor() as usize).min(last);
+    let j = (i + 1).min(last);
+    let t = position - i as f32;
+
+    spectrum[i] + t * (spectrum[j] - spectrum[i])
+}
+
+/// Averages the power of several spectra bin by bin, such as to fold per-channel spectra down to mono before bucketizing.
+pub fn average_spectra(spectra: &[Vec<SignalStrength>]) -> Result<Vec<SignalStrength>, Error> {
+    let len = spectra.first().map(|s| s.len()).unwrap_or(0);
+
+    let mut average = vec![0.0; len];
+
+    for spectrum in spectra {
+        if spectrum.len() != len { Err(Error::SpectrumLength(len, spectrum.len()))? }
+
+        for (a, p) in average.iter_mut().zip(spectrum) {
+            *a += p;
+        }
+    }
+
+    for a in average.iter_mut() {
+        *a /= spectra.len() as SignalStrength;
+    }
+
+    Ok(average)
+}
+
+/// Calculates the finest bin size achievable with a power-of-two FFT length whose window lasts at most `max_latency_ms`.
+pub fn best_resolution_for_latency(max_latency_ms: f32, sampling_rate: usize) -> Result<Frequency, Error> {
+    if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+    let max_len = (max_latency_ms / 1000.0 * sampling_rate as f32).floor() as usize;
+
+    if !(max_len > 0) { Err(Error::FrameSize)? }
+
+    // The largest power of two that fits.
+    let fft_len = 1usize << (usize::BITS - 1 - max_len.leading_zeros());
+
+    Ok(sampling_rate as Frequency / fft_len as Frequency)
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Analyzer::new(2048, WindowKind::Hanning)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestUtil;
+    use crate::wave::WaveFunction;
+    use crate::wave::WaveGen;
+
+    const SAMPLES_PER_PERIOD: usize = 44100;
+    const FREQUENCY: Frequency = 440.0;
+
+    #[test]
+    fn test_analyze_mono() {
+        const FFT_LEN: usize = 16;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+
+        let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+
+        assert_eq!(FFT_LEN, spectrum.len());
+
+        let expected: Vec<SignalStrength> = vec![
+            3.0186355,
+            0.31955782,
+            0.07949541,
+            0.03741721,
+            0.023034703,
+            0.016638935,
+            0.013468596,
+            0.011947523,
+            0.011491794,
+            0.011947523,
+            0.013468596,
+            0.016638935,
+            0.023034703,
+            0.03741721,
+            0.07949541,
+            0.31955782,
+        ];
+
+        for (e, ss) in expected.into_iter().zip(spectrum) {
+            assert_approx_eq!(e, ss);
+        }
+
+        // let fft_bin_size = SAMPLE_RATE as Frequency / FFT_LEN as f32;
+
+        // for (n, ss) in spectrum.iter().enumerate() {
+        //     println!("{}: {} ({} Hz)", n, ss, n as f32 * fft_bin_size);
+        // }
+
+        // println!("{:?}", analyzer.buckets());
+    }
+
+    #[test]
+    fn test_analyze_mono_large_window() {
+        const FFT_LEN: usize = 2048;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+
+        let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+
+        assert_eq!(FFT_LEN, spectrum.len());
+
+        let fft_bin_size = SAMPLES_PER_PERIOD as Frequency / FFT_LEN as f32;
+
+        let expected_max_spectrum_index = (FREQUENCY / fft_bin_size) as usize;
+
+        let produced_max_spectrum_index =
+            spectrum
+            .iter()
+            .take(FFT_LEN / 2)
+            .enumerate()
+            .max_by(|(_, sa), (_, sb)| sa.partial_cmp(&sb).unwrap())
+            .map(|(i, _)| i)
+            .unwrap()
+        ;
+
+        println!("{}, {}", expected_max_spectrum_index, produced_max_spectrum_index);
+        assert_eq!(expected_max_spectrum_index, produced_max_spectrum_index);
+
+        // for (n, ss) in spectrum.iter().enumerate() {
+        //     println!("{}: {} ({} Hz)", n, ss, n as f32 * fft_bin_size);
+        // }
+    }
+
+    #[test]
+    fn test_window_main_lobe() {
+        const FFT_LEN: usize = 256;
+
+        // Halfway between two bins, which is the worst case for leakage.
+        let frequency = 32.5 * SAMPLES_PER_PERIOD as Frequency / FFT_LEN as Frequency;
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, FFT_LEN));
+
+        // Minimum ratio in decibels between the main lobe and any bin well outside of it.
+        let kinds_and_expected = vec![
+            (WindowKind::Hanning, 30.0),
+            (WindowKind::Hamming, 40.0),
+            (WindowKind::Blackman, 55.0),
+        ];
+
+        for (kind, expected) in kinds_and_expected {
+            let mut analyzer = Analyzer::new(FFT_LEN, kind);
+            let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+
+            let main_lobe = spectrum[32].max(spectrum[33]);
+            let leakage =
+                (1..=(FFT_LEN / 2))
+                .filter(|&i| !(28..=37).contains(&i))
+                .map(|i| spectrum[i])
+                .fold(0.0, SignalStrength::max)
+            ;
+
+            assert!(10.0 * (main_lobe / leakage).log10() > expected);
+        }
+    }
+
+    #[test]
+    fn test_zero_crossing_rate() {
+        const FFT_LEN: usize = 1024;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // A sinusoid crosses zero twice per period.
+        for &frequency in &[100.0, 5000.0] {
+            let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, 2 * FFT_LEN);
+            let produced = analyzer.zero_crossing_rate(&samples).unwrap();
+            assert_approx_eq!(2.0 * frequency / SAMPLES_PER_PERIOD as f32, produced, 2.0 / FFT_LEN as f32);
+        }
+
+        assert_eq!(0.0, analyzer.zero_crossing_rate(&[0.5; FFT_LEN]).unwrap());
+        assert_eq!(Err(Error::TooFewSamples(FFT_LEN, 4)), analyzer.zero_crossing_rate(&[0.0; 4]));
+    }
+
+    #[test]
+    fn test_harmonic_structure() {
+        const FFT_LEN: usize = 4096;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Blackman);
+
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+        let fundamental = fft_bin_size * 20.0;
+
+        let mut harmonic_ratios = |function: WaveFunction| {
+            let samples: Vec<_> = WaveGen::new(function, SAMPLES_PER_PERIOD, fundamental).take(FFT_LEN).collect();
+            let spectrum = analyzer.analyze(&SampleBuffer::from(samples)).unwrap().0.to_vec();
+            let power = |h: usize| spectrum[20 * h - 2..=20 * h + 2].iter().sum::<SignalStrength>();
+            (power(2) / power(1), power(3) / power(1))
+        };
+
+        // A square wave only has odd harmonics.
+        let (second, third) = harmonic_ratios(WaveFunction::Square);
+        assert!(second < 1e-3);
+        assert!(third > 0.05);
+
+        // A sawtooth has all of them, falling off with the harmonic number.
+        let (second, third) = harmonic_ratios(WaveFunction::Sawtooth);
+        assert!(second > 0.1);
+        assert!(third > 0.05 && third < second);
+    }
+
+    #[test]
+    fn test_bin_frequency() {
+        const FFT_LEN: usize = 16;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+
+        assert_eq!(0.0, analyzer.bin_frequency(0, SAMPLES_PER_PERIOD).unwrap());
+        assert_approx_eq!(fft_bin_size, analyzer.bin_frequency(1, SAMPLES_PER_PERIOD).unwrap());
+        assert_approx_eq!((FFT_LEN - 1) as Frequency * fft_bin_size, analyzer.bin_frequency(FFT_LEN - 1, SAMPLES_PER_PERIOD).unwrap());
+        assert_eq!(Err(Error::BinIndex(FFT_LEN, FFT_LEN)), analyzer.bin_frequency(FFT_LEN, SAMPLES_PER_PERIOD));
+    }
+
+    #[test]
+    fn test_nyquist_bin() {
+        let analyzer = Analyzer::new(1024, WindowKind::Rectangular);
+
+        let (index, frequency) = analyzer.nyquist_bin(SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(512, index);
+        assert_approx_eq!(SAMPLES_PER_PERIOD as Frequency / 2.0, frequency);
+
+        // With an odd length, there is no bin exactly at Nyquist.
+        let analyzer = Analyzer::new(5, WindowKind::Rectangular);
+
+        let (index, frequency) = analyzer.nyquist_bin(10).unwrap();
+        assert_eq!(2, index);
+        assert_approx_eq!(4.0, frequency);
+    }
+
+    #[test]
+    fn test_dominant_frequency() {
+        const FFT_LEN: usize = 1024;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+
+        // Centered on a bin, so the tone does not straddle two bins.
+        let frequency = fft_bin_size * 10.0;
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, FFT_LEN));
+        let spectrum = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let (produced_frequency, confidence) = analyzer.dominant_frequency(&spectrum, SAMPLES_PER_PERIOD).unwrap().unwrap();
+        assert_approx_eq!(frequency, produced_frequency, 1e-3);
+        assert!(confidence > 0.5);
+
+        let samples = SampleBuffer::from(TestUtil::generate_noise_samples(FFT_LEN));
+        let spectrum = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let (_, confidence) = analyzer.dominant_frequency(&spectrum, SAMPLES_PER_PERIOD).unwrap().unwrap();
+        assert!(confidence < 0.1);
+
+        assert_eq!(None, analyzer.dominant_frequency(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD).unwrap());
+
+        // Between bins, interpolation recovers the tone to well within a bin.
+        for &offset in &[0.25, 0.5, 0.75] {
+            let frequency = fft_bin_size * (20.0 + offset);
+            let samples: Vec<_> = WaveGen::new(WaveFunction::Sine, SAMPLES_PER_PERIOD, frequency).take(FFT_LEN).collect();
+            let spectrum = analyzer.analyze(&SampleBuffer::from(samples)).unwrap().0.to_vec();
+
+            let (produced_frequency, _) = analyzer.dominant_frequency(&spectrum, SAMPLES_PER_PERIOD).unwrap().unwrap();
+            assert!((produced_frequency - frequency).abs() < fft_bin_size * 0.1);
+        }
+    }
+
+    #[test]
+    fn test_spectral_contrast() {
+        const FFT_LEN: usize = 1024;
+        const SUB_BANDS: usize = 6;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let frequency = analyzer.bin_frequency(100, SAMPLES_PER_PERIOD).unwrap();
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, FFT_LEN));
+        let tonal = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let samples = SampleBuffer::from(TestUtil::generate_noise_samples(FFT_LEN));
+        let noise = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let tonal_contrast = analyzer.spectral_contrast(&tonal, SUB_BANDS).unwrap();
+        let noise_contrast = analyzer.spectral_contrast(&noise, SUB_BANDS).unwrap();
+
+        assert_eq!(SUB_BANDS, tonal_contrast.len());
+        assert_eq!(SUB_BANDS, noise_contrast.len());
+
+        // Bin 100 falls in the fifth group, spanning bins 64 to 180.
+        assert!(tonal_contrast[4] > 60.0);
+        for c in noise_contrast {
+            assert!(c < 20.0);
+        }
+
+        assert_eq!(Err(Error::NumBands), analyzer.spectral_contrast(&tonal, 0));
+    }
+
+    #[test]
+    fn test_detect_pitch_cepstrum() {
+        const FFT_LEN: usize = 2048;
+        const FUNDAMENTAL: Frequency = 220.0;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // A sawtooth-like tone with decaying harmonics.
+        let mut harmonic = vec![0.0; FFT_LEN];
+        for k in 1..=10 {
+            let partial = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FUNDAMENTAL * k as Frequency, FFT_LEN);
+            for (h, p) in harmonic.iter_mut().zip(partial) {
+                *h += p / k as Sample;
+            }
+        }
+
+        let spectrum = analyzer.analyze(&SampleBuffer::from(harmonic)).unwrap().0.to_vec();
+
+        let produced = analyzer.detect_pitch_cepstrum(&spectrum, SAMPLES_PER_PERIOD).unwrap().unwrap();
+        assert!((produced - FUNDAMENTAL).abs() < 2.0);
+
+        assert_eq!(None, analyzer.detect_pitch_cepstrum(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD).unwrap());
+    }
+
+    #[test]
+    fn test_spectral_centroid() {
+        const FFT_LEN: usize = 1024;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+
+        let spectrum = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+        let centroid = analyzer.spectral_centroid(&spectrum, SAMPLES_PER_PERIOD).unwrap();
+
+        // A pure tone has its centroid close to the tone itself.
+        assert!((centroid - FREQUENCY).abs() < fft_bin_size);
+
+        assert_eq!(0.0, analyzer.spectral_centroid(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD).unwrap());
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.spectral_centroid(&[0.0; 4], SAMPLES_PER_PERIOD));
+        assert_eq!(Err(Error::SamplingRate(0)), analyzer.spectral_centroid(&spectrum, 0));
+    }
+
+    #[test]
+    fn test_onset_function() {
+        const FFT_LEN: usize = 256;
+        const HOP: usize = 64;
+        const CLICK_PERIOD: usize = 2048;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // Single-sample clicks over silence.
+        let mut samples = vec![0.0; 8 * CLICK_PERIOD];
+        let clicks: Vec<_> = (0..8).map(|k| CLICK_PERIOD / 2 + k * CLICK_PERIOD).collect();
+        for &c in &clicks {
+            samples[c] = 1.0;
+        }
+
+        let produced = analyzer.onset_function(&samples, HOP).unwrap();
+        assert_eq!(spectrogram::frame_count(samples.len(), FFT_LEN, HOP).unwrap(), produced.len());
+
+        // Within each click's neighborhood, the strongest onset is in a frame that contains the click.
+        for &c in &clicks {
+            let first = (c - CLICK_PERIOD / 2) / HOP;
+            let last = ((c + CLICK_PERIOD / 2) / HOP).min(produced.len());
+
+            let peak =
+                (first..last)
+                .max_by(|&a, &b| produced[a].partial_cmp(&produced[b]).unwrap())
+                .unwrap()
+            ;
+
+            assert!((peak * HOP..peak * HOP + FFT_LEN).contains(&c));
+            assert!(produced[peak] > 0.0);
+        }
+
+        // Nothing changes in the silence before the first click.
+        for f in &produced[..(clicks[0] - FFT_LEN) / HOP] {
+            assert_eq!(0.0, *f);
+        }
+
+        assert_eq!(Err(Error::HopSize), analyzer.onset_function(&samples, 0));
+    }
+
+    #[test]
+    fn test_energy_per_octave() {
+        const FFT_LEN: usize = 8192;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+
+        // Pink noise has a power spectral density proportional to `1 / f`.
+        let spectrum: Vec<_> = (0..FFT_LEN).map(|i| if i == 0 { 0.0 } else { 1.0 / (fft_bin_size * i as Frequency) }).collect();
+
+        let produced = analyzer.energy_per_octave(&spectrum, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(10, produced.len());
+
+        assert_approx_eq!(31.25, produced[0].0);
+        assert_approx_eq!(16000.0, produced[9].0);
+        assert_approx_eq!(1.0, produced.iter().map(|(_, s)| s).sum::<f32>());
+
+        // Each octave holds roughly the same share.
+        let expected = 1.0 / produced.len() as f32;
+        for (_, share) in &produced {
+            assert!((share - expected).abs() < expected * 0.2);
+        }
+
+        // Octaves above Nyquist are omitted.
+        assert_eq!(8, analyzer.energy_per_octave(&spectrum, 11025).unwrap().len());
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.energy_per_octave(&[0.0; 4], SAMPLES_PER_PERIOD));
+    }
+
+    #[test]
+    fn test_calculate_weighted_spectrum() {
+        const FFT_LEN: usize = 640;
+        const SAMPLING_RATE: usize = 32000;
+
+        // Bins are 50 Hz wide, so that both tones are centered on one.
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        let samples: Vec<_> =
+            TestUtil::generate_wave_samples(SAMPLING_RATE, 50.0, FFT_LEN).into_iter()
+            .zip(TestUtil::generate_wave_samples(SAMPLING_RATE, 1000.0, FFT_LEN))
+            .map(|(low, high)| low + high)
+            .collect()
+        ;
+
+        let flat = analyzer.analyze_multi(&[&samples]).unwrap().remove(0);
+        let produced = analyzer.calculate_weighted_spectrum(&samples, SAMPLING_RATE, Weighting::A).unwrap();
+
+        assert_eq!(FFT_LEN, produced.len());
+        assert_approx_eq!(1.0, produced[20] / flat[20], 1e-3);
+        assert_approx_eq!(-30.2, 10.0 * (produced[1] / flat[1]).log10(), 0.1);
+
+        // The mirrored bins are weighted the same.
+        assert_eq!(produced[1], produced[FFT_LEN - 1]);
+        assert_eq!(0.0, produced[0]);
+
+        assert_eq!(flat, analyzer.calculate_weighted_spectrum(&samples, SAMPLING_RATE, Weighting::Z).unwrap());
+        assert_eq!(Err(Error::SamplingRate(0)), analyzer.calculate_weighted_spectrum(&samples, 0, Weighting::A));
+    }
+
+    #[test]
+    fn test_deleak_buckets() {
+        const FFT_LEN: usize = 1024;
+
+        let layout = Buckets::new(20.0, 20000.0, 48).unwrap();
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // A tone at the center of a bucket, which leaks into both neighbors.
+        let index = layout.locate(1000.0).unwrap();
+        let (lo, hi) = layout.bands()[index];
+        let tone = (lo * hi).sqrt();
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, tone, FFT_LEN));
+        let spectrum = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let leaky = layout.bucketize(&spectrum, SAMPLES_PER_PERIOD).unwrap();
+        assert!(leaky[index - 1] > 0.0 && leaky[index + 1] > 0.0);
+
+        let mut produced = leaky.clone();
+        analyzer.deleak_buckets(&layout, SAMPLES_PER_PERIOD, &mut produced).unwrap();
+
+        assert_eq!(leaky[index], produced[index]);
+        assert!(produced[index - 1] < leaky[index - 1] / 2.0);
+        assert!(produced[index + 1] < leaky[index + 1] / 2.0);
+        assert!(produced.iter().all(|b| *b >= 0.0));
+
+        assert_eq!(Err(Error::BucketsLength(48, 2)), analyzer.deleak_buckets(&layout, SAMPLES_PER_PERIOD, &mut [0.0; 2]));
+    }
+
+    #[test]
+    fn test_third_octave_spl() {
+        const FFT_LEN: usize = 1024;
+        const SAMPLING_RATE: usize = 32000;
+        const OFFSET_DB: f32 = 94.0;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular).with_amplitude_normalization(true);
+
+        // A 1 kHz tone, centered on a bin, with an amplitude of 0.25 has a power of 1/16.
+        let samples = TestUtil::generate_wave_samples(SAMPLING_RATE, 1000.0, FFT_LEN);
+        let spectrum = analyzer.analyze(&SampleBuffer::from(samples)).unwrap().0.to_vec();
+
+        let produced = analyzer.third_octave_spl(&spectrum, SAMPLING_RATE, OFFSET_DB).unwrap();
+
+        // Only the band centered at 20 kHz is above the Nyquist frequency.
+        assert_eq!(30, produced.len());
+
+        let (center, spl) = produced[17];
+        assert_approx_eq!(1000.0, center, 1e-2);
+        assert_approx_eq!(OFFSET_DB + 10.0 * (1.0f32 / 16.0).log10(), spl, 1e-3);
+
+        for (i, (_, other)) in produced.iter().enumerate() {
+            if i != 17 { assert!(*other < spl - 60.0); }
+        }
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.third_octave_spl(&[0.0; 4], SAMPLING_RATE, OFFSET_DB));
+    }
+
+    #[test]
+    fn test_thd() {
+        const FFT_LEN: usize = 4096;
+        const FUNDAMENTAL: Frequency = 1000.0;
+        const HARMONIC_LEVEL: f32 = 0.1;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let fundamental = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FUNDAMENTAL, FFT_LEN);
+        let harmonic = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 2.0 * FUNDAMENTAL, FFT_LEN);
+        let distorted: Vec<_> = fundamental.iter().zip(&harmonic).map(|(f, h)| f + HARMONIC_LEVEL * h).collect();
+
+        let spectrum = analyzer.analyze(&SampleBuffer::from(distorted)).unwrap().0.to_vec();
+        let produced = analyzer.thd(&spectrum, SAMPLES_PER_PERIOD, FUNDAMENTAL, 5).unwrap();
+        assert_approx_eq!(HARMONIC_LEVEL * 100.0, produced, 0.2);
+
+        // A pure tone has practically no distortion.
+        let spectrum = analyzer.analyze(&SampleBuffer::from(fundamental)).unwrap().0.to_vec();
+        assert!(analyzer.thd(&spectrum, SAMPLES_PER_PERIOD, FUNDAMENTAL, 5).unwrap() < 0.1);
+
+        assert_eq!(0.0, analyzer.thd(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD, FUNDAMENTAL, 5).unwrap());
+        assert_eq!(Err(Error::Fundamental), analyzer.thd(&spectrum, SAMPLES_PER_PERIOD, 0.0, 5));
+        assert_eq!(Err(Error::Fundamental), analyzer.thd(&spectrum, SAMPLES_PER_PERIOD, 30000.0, 5));
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.thd(&[0.0; 4], SAMPLES_PER_PERIOD, FUNDAMENTAL, 5));
+    }
+
+    #[test]
+    fn test_spectrum_rms() {
+        const FFT_LEN: usize = 8;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // The zero frequency and the mirrored upper half are left out.
+        let spectrum = vec![100.0, 1.0, 4.0, 9.0, 2.0, 100.0, 100.0, 100.0];
+        assert_approx_eq!(4.0f32.sqrt(), analyzer.spectrum_rms(&spectrum).unwrap());
+
+        assert_eq!(0.0, analyzer.spectrum_rms(&[0.0; FFT_LEN]).unwrap());
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.spectrum_rms(&[0.0; 4]));
+    }
+
+    #[test]
+    fn test_log_mel() {
+        const FFT_LEN: usize = 1024;
+        const N_MELS: usize = 40;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+        let spectrum = analyzer.analyze(&samples).unwrap().0.to_vec();
+
+        let produced = analyzer.log_mel(&spectrum, SAMPLES_PER_PERIOD, N_MELS).unwrap();
+        assert_eq!(N_MELS, produced.len());
+        assert!(produced.iter().all(|v| v.is_finite()));
+
+        // The loudest filter is the one nearest the tone on the mel scale.
+        let loudest =
+            (0..N_MELS)
+            .max_by(|&a, &b| produced[a].partial_cmp(&produced[b]).unwrap())
+            .unwrap()
+        ;
+        let mel_spacing = mel::hz_to_mel(SAMPLES_PER_PERIOD as Frequency / 2.0) / (N_MELS + 1) as f32;
+        let center = mel_spacing * (loudest + 1) as f32;
+        assert!((center - mel::hz_to_mel(FREQUENCY)).abs() < mel_spacing);
+
+        let silent = analyzer.log_mel(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD, N_MELS).unwrap();
+        assert!(silent.iter().all(|v| v.is_finite()));
+
+        assert_eq!(Err(Error::NumBands), analyzer.log_mel(&spectrum, SAMPLES_PER_PERIOD, 0));
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.log_mel(&[0.0; 4], SAMPLES_PER_PERIOD, N_MELS));
+    }
+
+    #[test]
+    fn test_mfcc() {
+        const FFT_LEN: usize = 1024;
+        const N_MELS: usize = 40;
+        const N_COEFFS: usize = 13;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let samples = TestUtil::generate_noise_samples(FFT_LEN);
+        let spectrum = analyzer.analyze(&SampleBuffer::from(samples.clone())).unwrap().0.to_vec();
+
+        let produced = analyzer.mfcc(&spectrum, SAMPLES_PER_PERIOD, N_MELS, N_COEFFS).unwrap();
+        assert_eq!(N_COEFFS, produced.len());
+
+        let log_mel = analyzer.log_mel(&spectrum, SAMPLES_PER_PERIOD, N_MELS).unwrap();
+        assert_approx_eq!(log_mel.iter().sum::<f32>(), produced[0], 1e-2);
+
+        // Making the signal ten times louder raises every log-mel feature by ln(100),
+        // which only changes the zeroth coefficient.
+        let louder: Vec<_> = samples.iter().map(|s| s * 10.0).collect();
+        let spectrum = analyzer.analyze(&SampleBuffer::from(louder)).unwrap().0.to_vec();
+        let louder_produced = analyzer.mfcc(&spectrum, SAMPLES_PER_PERIOD, N_MELS, N_COEFFS).unwrap();
+
+        assert_approx_eq!(produced[0] + N_MELS as f32 * 100.0f32.ln(), louder_produced[0], 1e-2);
+        for (p, l) in produced.iter().zip(&louder_produced).skip(1) {
+            assert_approx_eq!(p, l, 1e-2);
+        }
+
+        assert_eq!(Err(Error::NumCoefficients(0, N_MELS)), analyzer.mfcc(&spectrum, SAMPLES_PER_PERIOD, N_MELS, 0));
+        assert_eq!(Err(Error::NumCoefficients(41, N_MELS)), analyzer.mfcc(&spectrum, SAMPLES_PER_PERIOD, N_MELS, 41));
+    }
+
+    #[test]
+    fn test_noise_floor() {
+        const FFT_LEN: usize = 1024;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let noise = TestUtil::generate_noise_samples(FFT_LEN);
+        let tone = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let mixed: Vec<_> = noise.iter().zip(&tone).map(|(n, t)| n + t).collect();
+
+        let noise_spectrum = analyzer.analyze(&SampleBuffer::from(noise)).unwrap().0.to_vec();
+        let mixed_spectrum = analyzer.analyze(&SampleBuffer::from(mixed)).unwrap().0.to_vec();
+
+        let noise_floor = analyzer.noise_floor(&noise_spectrum).unwrap();
+        let produced = analyzer.noise_floor(&mixed_spectrum).unwrap();
+
+        // The tone barely moves the estimate, which stays far below the tone's peak.
+        let peak = mixed_spectrum.iter().cloned().fold(0.0, SignalStrength::max);
+        assert!(produced > noise_floor / 2.0 && produced < noise_floor * 2.0);
+        assert!(produced < peak / 100.0);
+
+        assert_eq!(0.0, analyzer.noise_floor(&vec![0.0; FFT_LEN]).unwrap());
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.noise_floor(&[0.0; 4]));
+    }
+
+    #[test]
+    fn test_process() {
+        const FFT_LEN: usize = 256;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        // A fresh buffer is silent.
+        let produced = analyzer.process(&SampleBuffer::new(FFT_LEN), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(vec![0.0; buckets.len()], produced);
+
+        // The same as analyzing and bucketizing by hand.
+        let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let (spectrum_l, spectrum_r) = analyzer.analyze(&SampleBuffer::from(samples.clone())).unwrap();
+        let mono: Vec<_> = spectrum_l.iter().zip(spectrum_r).map(|(l, r)| (l + r) / 2.0).collect();
+        let expected = buckets.bucketize(&mono, SAMPLES_PER_PERIOD).unwrap();
+
+        let produced = analyzer.process(&SampleBuffer::from(samples.clone()), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(expected, produced);
+
+        // Longer buffers only use their latest samples.
+        let mut longer = vec![1.0; FFT_LEN];
+        longer.extend_from_slice(&samples);
+        let produced = analyzer.process(&SampleBuffer::from(longer), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(expected, produced);
+
+        // Shorter buffers are padded with silence at the start.
+        let mut padded = vec![0.0; FFT_LEN / 2];
+        padded.extend_from_slice(&samples[FFT_LEN / 2..]);
+        let expected = analyzer.process(&SampleBuffer::from(padded), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        let produced = analyzer.process(&SampleBuffer::from(samples[FFT_LEN / 2..].to_vec()), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_spectrum_with_axis() {
+        const FFT_LEN: usize = 1024;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let (axis, spectrum) = analyzer.spectrum_with_axis(&samples, SAMPLES_PER_PERIOD).unwrap();
+
+        assert_eq!(FFT_LEN / 2 + 1, axis.len());
+        assert_eq!(axis.len(), spectrum.len());
+
+        assert_eq!(0.0, axis[0]);
+        assert!(axis.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_approx_eq!(SAMPLES_PER_PERIOD as Frequency / 2.0, axis[FFT_LEN / 2], 1e-2);
+
+        // The same as the first half of the full spectrum.
+        let (expected, _) = analyzer.analyze(&SampleBuffer::from(samples)).unwrap();
+        assert_eq!(&expected[..=FFT_LEN / 2], spectrum.as_slice());
+
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 4)), analyzer.spectrum_with_axis(&[0.0; 4], SAMPLES_PER_PERIOD));
+    }
+
+    #[test]
+    fn test_detilt() {
+        const FFT_LEN: usize = 512;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // Falling at 6 dB per octave, which is power inversely proportional to frequency.
+        let spectrum: Vec<_> = (0..FFT_LEN).map(|i| 1.0 / i.min(FFT_LEN - i).max(1) as SignalStrength).collect();
+
+        let produced = analyzer.detilt(&spectrum).unwrap();
+
+        let valid = &produced[1..=FFT_LEN / 2];
+        let (min, max) = valid.iter().fold((SignalStrength::INFINITY, 0.0f32), |(lo, hi), &p| (lo.min(p), hi.max(p)));
+        assert!(10.0 * (max / min).log10() < 0.01);
+
+        // The result is still symmetric, and the zero frequency is untouched.
+        for i in 1..FFT_LEN / 2 {
+            assert_approx_eq!(produced[i], produced[FFT_LEN - i], 1e-6);
+        }
+        assert_eq!(spectrum[0], produced[0]);
+
+        // Silence and flat spectra are left alone.
+        assert_eq!(vec![0.0; FFT_LEN], analyzer.detilt(&vec![0.0; FFT_LEN]).unwrap());
+        for p in analyzer.detilt(&vec![0.5; FFT_LEN]).unwrap() {
+            assert_approx_eq!(0.5, p, 1e-6);
+        }
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.detilt(&[0.0; 4]));
+    }
+
+    #[test]
+    fn test_inverse_fft() {
+        const FFT_LEN: usize = 256;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+        let backend = RustFftBackend::new(FFT_LEN);
+
+        let samples: Vec<_> = WaveGen::new(WaveFunction::Sine, SAMPLES_PER_PERIOD, FREQUENCY).with_amplitude(1.0).take(FFT_LEN).collect();
+
+        let mut input: Vec<_> = samples.iter().map(|&s| Complex::new(s, 0.0)).collect();
+        let mut spectrum = vec![Complex::zero(); FFT_LEN];
+        backend.forward(&mut input, &mut spectrum);
+
+        // The round trip keeps the original amplitude, and the phase, so the imaginary part cancels out.
+        let produced = analyzer.inverse_fft(&spectrum).unwrap();
+        for (s, p) in samples.iter().zip(&produced) {
+            assert_approx_eq!(s, p.re, 1e-5);
+            assert_approx_eq!(0.0, p.im, 1e-5);
+        }
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.inverse_fft(&[Complex::zero(); 4]));
+    }
+
+    #[test]
+    fn test_crest_per_octave() {
+        const FFT_LEN: usize = 4096;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let noise = TestUtil::generate_noise_samples(FFT_LEN);
+        let noise_spectrum = analyzer.analyze(&SampleBuffer::from(noise.clone())).unwrap().0.to_vec();
+
+        // A loud tone in the 1 kHz octave.
+        let tone = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 1000.0, FFT_LEN);
+        let mixed: Vec<_> = noise.iter().zip(&tone).map(|(n, t)| n * 0.1 + t).collect();
+        let mixed_spectrum = analyzer.analyze(&SampleBuffer::from(mixed)).unwrap().0.to_vec();
+
+        let expected = analyzer.energy_per_octave(&mixed_spectrum, SAMPLES_PER_PERIOD).unwrap();
+        let produced = analyzer.crest_per_octave(&mixed_spectrum, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(expected.len(), produced.len());
+
+        for ((ec, _), (pc, crest)) in expected.iter().zip(&produced) {
+            assert_eq!(ec, pc);
+
+            // Only the octave with the tone is peaky, while the wider noise-only octaves stay flat.
+            if *pc == 1000.0 { assert!(*crest > 20.0) }
+            else if *pc >= 250.0 { assert!(*crest < 20.0) }
+        }
+
+        let noise_crests = analyzer.crest_per_octave(&noise_spectrum, SAMPLES_PER_PERIOD).unwrap();
+        let (_, noise_crest) = noise_crests.iter().find(|(c, _)| *c == 1000.0).unwrap();
+        let (_, tone_crest) = produced.iter().find(|(c, _)| *c == 1000.0).unwrap();
+        assert!(tone_crest > &(noise_crest * 4.0));
+
+        for (_, crest) in analyzer.crest_per_octave(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD).unwrap() {
+            assert_eq!(0.0, crest);
+        }
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.crest_per_octave(&[0.0; 4], SAMPLES_PER_PERIOD));
+    }
+
+    #[test]
+    fn test_resample_to_grid() {
+        const FFT_LEN: usize = 4096;
+
+        // A grid every 50 Hz, shared between sampling rates.
+        let grid: Vec<_> = (1..=400).map(|i| i as Frequency * 50.0).collect();
+
+        for &sampling_rate in &[44100, 48000] {
+            let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+            let samples = TestUtil::generate_wave_samples(sampling_rate, 1010.0, FFT_LEN);
+            let spectrum = analyzer.analyze(&SampleBuffer::from(samples)).unwrap().0.to_vec();
+
+            let produced = analyzer.resample_to_grid(&spectrum, sampling_rate, &grid).unwrap();
+            assert_eq!(grid.len(), produced.len());
+
+            // The tone lands on the 1 kHz grid point.
+            let loudest = (0..grid.len()).max_by(|&a, &b| produced[a].partial_cmp(&produced[b]).unwrap()).unwrap();
+            assert_eq!(1000.0, grid[loudest]);
+        }
+
+        let analyzer = Analyzer::new(4, WindowKind::Hanning);
+        assert_eq!(vec![0.0, 0.5, 1.0, 2.0, 2.0], analyzer.resample_to_grid(&[0.0, 1.0, 2.0, 1.0], 4, &[0.0, 0.5, 1.0, 2.0, 3.0]).unwrap());
+        assert_eq!(Err(Error::SpectrumLength(4, 1)), analyzer.resample_to_grid(&[0.0], 4, &grid));
+    }
+
+    #[test]
+    fn test_weighting_delta() {
+        const FFT_LEN: usize = 4096;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // A-weighting (almost) leaves 1 kHz alone, and cuts 50 Hz by about 30 dB.
+        let mid = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 1000.0, FFT_LEN);
+        assert!(analyzer.weighting_delta(&mid, SAMPLES_PER_PERIOD).unwrap().abs() < 0.5);
+
+        let bass = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 50.0, FFT_LEN);
+        let produced = analyzer.weighting_delta(&bass, SAMPLES_PER_PERIOD).unwrap();
+        assert!(produced < -25.0 && produced > -35.0);
+
+        assert_eq!(0.0, analyzer.weighting_delta(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD).unwrap());
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 4)), analyzer.weighting_delta(&[0.0; 4], SAMPLES_PER_PERIOD));
+    }
+
+    #[test]
+    fn test_impulse_response_spectrum() {
+        const FFT_LEN: usize = 64;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+        let produced = analyzer.impulse_response_spectrum().unwrap();
+
+        assert_eq!(FFT_LEN, produced.len());
+        for p in produced {
+            assert_approx_eq!(1.0, p, 1e-6);
+        }
+
+        // Other windows scale the response, and pre-emphasis tilts it.
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let center = analyzer.window_coefficients()[FFT_LEN / 2];
+        for p in analyzer.impulse_response_spectrum().unwrap() {
+            assert_approx_eq!(center.powi(2), p, 1e-6);
+        }
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular).with_pre_emphasis(0.97);
+        let produced = analyzer.impulse_response_spectrum().unwrap();
+        assert!(produced[1] < produced[FFT_LEN / 2]);
+    }
+
+    #[test]
+    fn test_brightness() {
+        const FFT_LEN: usize = 4096;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let mut brightness_of = |frequency: Frequency| {
+            let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, FFT_LEN);
+            let spectrum = analyzer.analyze(&SampleBuffer::from(samples)).unwrap().0.to_vec();
+            analyzer.brightness(&spectrum, SAMPLES_PER_PERIOD, 20.0, 20000.0).unwrap()
+        };
+
+        assert!(brightness_of(50.0) < 0.2);
+        assert!(brightness_of(15000.0) > 0.9);
+
+        // 632 Hz is halfway between the cutoffs on a log scale.
+        assert_approx_eq!(0.5, brightness_of(632.5), 0.02);
+
+        assert_eq!(0.0, analyzer.brightness(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD, 20.0, 20000.0).unwrap());
+        assert_eq!(Err(Error::CutoffOrder), analyzer.brightness(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD, 200.0, 20.0));
+    }
+
+    #[test]
+    fn test_subsonic_energy() {
+        const FFT_LEN: usize = 16384;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let tone = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let rumble = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 10.0, FFT_LEN);
+        let mixed: Vec<_> = tone.iter().zip(&rumble).map(|(t, r)| t + r).collect();
+
+        let clean_spectrum = analyzer.analyze(&SampleBuffer::from(tone)).unwrap().0.to_vec();
+        let mixed_spectrum = analyzer.analyze(&SampleBuffer::from(mixed)).unwrap().0.to_vec();
+
+        let clean = analyzer.subsonic_energy(&clean_spectrum, SAMPLES_PER_PERIOD, 20.0).unwrap();
+        let produced = analyzer.subsonic_energy(&mixed_spectrum, SAMPLES_PER_PERIOD, 20.0).unwrap();
+
+        assert!(produced > 0.0);
+        assert!(clean < produced * 1e-4);
+
+        // Everything is below a cutoff above the Nyquist frequency, while a zero cutoff excludes everything.
+        let total: SignalStrength = mixed_spectrum[..=FFT_LEN / 2].iter().sum();
+        assert_eq!(total, analyzer.subsonic_energy(&mixed_spectrum, SAMPLES_PER_PERIOD, 30000.0).unwrap());
+        assert_eq!(0.0, analyzer.subsonic_energy(&mixed_spectrum, SAMPLES_PER_PERIOD, 0.0).unwrap());
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.subsonic_energy(&[0.0; 4], SAMPLES_PER_PERIOD, 20.0));
+    }
+
+    #[test]
+    fn test_zoom_spectrum() {
+        const FFT_LEN: usize = 1024;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        // Two tones 10 Hz apart, well within a single plain bin of about 43 Hz.
+        let len = FFT_LEN * 110;
+        let low = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 1000.0, len);
+        let high = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 1010.0, len);
+        let mixed: Vec<_> = low.iter().zip(&high).map(|(l, h)| l + h).collect();
+
+        let is_peak = |powers: &[SignalStrength], i: usize| powers[i] > powers[i - 1] && powers[i] >= powers[i + 1];
+
+        // The plain spectrum shows a single peak.
+        let plain = analyzer.analyze(&SampleBuffer::from(mixed[len - FFT_LEN..].to_vec())).unwrap().0.to_vec();
+        assert_eq!(1, (1..FFT_LEN / 2).filter(|&i| is_peak(&plain, i) && plain[i] > 1.0).count());
+
+        let produced = analyzer.zoom_spectrum(&mixed, SAMPLES_PER_PERIOD, 1005.0, 400.0).unwrap();
+        assert_eq!(FFT_LEN, produced.len());
+        assert!(produced.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        let (frequencies, powers): (Vec<_>, Vec<_>) = produced.into_iter().unzip();
+        let max_power = powers.iter().cloned().fold(0.0, SignalStrength::max);
+
+        // The zoomed spectrum resolves both, with a deep dip in between.
+        let peaks: Vec<_> = (1..FFT_LEN - 1).filter(|&i| is_peak(&powers, i) && powers[i] > max_power / 10.0).map(|i| frequencies[i]).collect();
+        assert_eq!(2, peaks.len());
+        assert_approx_eq!(1000.0, peaks[0], 0.5);
+        assert_approx_eq!(1010.0, peaks[1], 0.5);
+
+        let middle = frequencies.iter().position(|&f| f >= 1005.0).unwrap();
+        assert!(powers[middle] < max_power / 100.0);
+
+        assert_eq!(Err(Error::TooFewSamples(FFT_LEN * 110, 4)), analyzer.zoom_spectrum(&[0.0; 4], SAMPLES_PER_PERIOD, 1005.0, 400.0));
+        assert_eq!(Err(Error::ZoomSpan), analyzer.zoom_spectrum(&mixed, SAMPLES_PER_PERIOD, 1005.0, 0.0));
+    }
+
+    #[test]
+    fn test_effective_resolution() {
+        const FFT_LEN: usize = 4096;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(fft_bin_size, analyzer.effective_resolution(SAMPLES_PER_PERIOD).unwrap());
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        assert_approx_eq!(1.5 * fft_bin_size, analyzer.effective_resolution(SAMPLES_PER_PERIOD).unwrap(), 1e-2);
+
+        assert_eq!(Err(Error::SamplingRate(0)), analyzer.effective_resolution(0));
+    }
+
+    #[test]
+    fn test_log_resample() {
+        const FFT_LEN: usize = 16;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        // Bins are 1 kHz apart, with power rising by one per bin.
+        let spectrum: Vec<_> = (0..FFT_LEN).map(|i| i as SignalStrength).collect();
+
+        let produced = analyzer.log_resample(&spectrum, 16000, 1000.0, 16000.0, 5).unwrap();
+        assert_eq!(5, produced.len());
+
+        // Each point is an octave above the last, and powers between bins are interpolated.
+        let expected = [(1000.0, 1.0), (2000.0, 2.0), (4000.0, 4.0), (8000.0, 8.0), (16000.0, 8.0)];
+        for ((ef, ep), (pf, pp)) in expected.iter().zip(&produced) {
+            assert_approx_eq!(ef, pf, 1e-2);
+            assert_approx_eq!(ep, pp, 1e-4);
+        }
+
+        // Halfway through an octave on a log scale lies between two bins.
+        let produced = analyzer.log_resample(&spectrum, 16000, 1000.0, 2000.0, 3).unwrap();
+        assert_approx_eq!(1000.0 * std::f32::consts::SQRT_2, produced[1].0, 1e-2);
+        assert_approx_eq!(std::f32::consts::SQRT_2, produced[1].1, 1e-4);
+
+        assert_eq!(Err(Error::CutoffOrder), analyzer.log_resample(&spectrum, 16000, 4000.0, 1000.0, 3));
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.log_resample(&[0.0; 4], 16000, 1000.0, 4000.0, 3));
+    }
+
+    #[test]
+    fn test_significant_peaks() {
+        const FFT_LEN: usize = 1024;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let noise = TestUtil::generate_noise_samples(FFT_LEN);
+        let tone = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let mixed: Vec<_> = noise.iter().zip(&tone).map(|(n, t)| n + t).collect();
+
+        let spectrum = analyzer.analyze(&SampleBuffer::from(mixed)).unwrap().0.to_vec();
+        let fft_bin_size = analyzer.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+
+        // Only the tone clears the margin, even though the noise has plenty of local maxima.
+        let produced = analyzer.significant_peaks(&spectrum, SAMPLES_PER_PERIOD, 20.0).unwrap();
+        assert_eq!(1, produced.len());
+        assert!((produced[0].0 - FREQUENCY).abs() < fft_bin_size);
+
+        assert!(analyzer.significant_peaks(&spectrum, SAMPLES_PER_PERIOD, 0.0).unwrap().len() > 1);
+
+        assert_eq!(Vec::<(Frequency, SignalStrength)>::new(), analyzer.significant_peaks(&vec![0.0; FFT_LEN], SAMPLES_PER_PERIOD, 20.0).unwrap());
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), analyzer.significant_peaks(&[0.0; 4], SAMPLES_PER_PERIOD, 20.0));
+    }
+
+    #[test]
+    fn test_best_resolution_for_latency() {
+        // 100 ms at 44.1 kHz is 4410 samples, so the FFT length is 4096.
+        assert_approx_eq!(44100.0 / 4096.0, best_resolution_for_latency(100.0, SAMPLES_PER_PERIOD).unwrap());
+        assert_approx_eq!(44100.0 / 2048.0, best_resolution_for_latency(50.0, SAMPLES_PER_PERIOD).unwrap());
+
+        // Tighter latency budgets give coarser resolution.
+        let mut prev = 0.0;
+        for &latency_ms in &[200.0, 100.0, 20.0, 5.0, 1.0] {
+            let produced = best_resolution_for_latency(latency_ms, SAMPLES_PER_PERIOD).unwrap();
+            assert!(produced > prev);
+            prev = produced;
+        }
+
+        assert_eq!(Err(Error::FrameSize), best_resolution_for_latency(0.01, SAMPLES_PER_PERIOD));
+        assert_eq!(Err(Error::SamplingRate(0)), best_resolution_for_latency(100.0, 0));
+    }
+
+    #[test]
+    fn test_analyze_in_place() {
+        const FFT_LEN: usize = 256;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning).with_amplitude_normalization(true);
+
+        let wave = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let noise = TestUtil::generate_noise_samples(FFT_LEN);
+
+        let expected = analyzer.analyze_multi(&[&wave, &noise]).unwrap();
+
+        let first = analyzer.analyze_in_place(&wave, Vec::new()).unwrap();
+        assert_eq!(expected[0], first);
+
+        // Passing each spectrum back alternates between the same two allocations.
+        let storage = first.as_ptr();
+
+        let second = analyzer.analyze_in_place(&noise, first).unwrap();
+        assert_eq!(expected[1], second);
+
+        let third = analyzer.analyze_in_place(&wave, second).unwrap();
+        assert_eq!(expected[0], third);
+        assert_eq!(storage, third.as_ptr());
+
+        // A buffer too small to hold the FFT output is dropped rather than reused.
+        assert_eq!(expected[1], analyzer.analyze_in_place(&noise, vec![0.0; 3]).unwrap());
+
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 4)), analyzer.analyze_in_place(&[0.0; 4], Vec::new()));
+    }
+
+    #[test]
+    fn test_analyze_multi() {
+        const FFT_LEN: usize = 256;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let wave = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let noise = TestUtil::generate_noise_samples(FFT_LEN);
+        let silence = vec![0.0; FFT_LEN];
+
+        let produced = analyzer.analyze_multi(&[&wave, &noise, &silence]).unwrap();
+        assert_eq!(3, produced.len());
+
+        // Each channel matches the stereo path.
+        let stereo = SampleBuffer::from(wave.iter().cloned().zip(noise.iter().cloned()).collect::<Vec<_>>());
+        let (expected_l, expected_r) = analyzer.analyze(&stereo).unwrap();
+        assert_eq!(expected_l, produced[0].as_slice());
+        assert_eq!(expected_r, produced[1].as_slice());
+        assert_eq!(silence, produced[2]);
+
+        let mono = average_spectra(&produced).unwrap();
+        for (i, m) in mono.iter().enumerate() {
+            assert_approx_eq!((produced[0][i] + produced[1][i]) / 3.0, m, 1e-4 * m.max(1.0));
+        }
+
+        assert_eq!(Vec::<Vec<SignalStrength>>::new(), analyzer.analyze_multi(&[]).unwrap());
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 4)), analyzer.analyze_multi(&[&wave, &[0.0; 4]]));
+        assert_eq!(Err(Error::SpectrumLength(2, 1)), average_spectra(&[vec![0.0; 2], vec![0.0]]));
+    }
+
+    #[test]
+    fn test_analyze_complex() {
+        use std::f32::consts::PI;
+
+        const FFT_LEN: usize = 64;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        for &bins in &[5i32, -5] {
+            let samples: Vec<_> =
+                (0..FFT_LEN)
+                .map(|n| Complex::from_polar(&1.0, &(2.0 * PI * (bins * n as i32) as f32 / FFT_LEN as f32)))
+                .collect()
+            ;
+
+            let spectrum = analyzer.analyze_complex(&samples).unwrap();
+
+            // Negative frequencies wrap around to the top of the spectrum.
+            let expected_index = (bins + FFT_LEN as i32) as usize % FFT_LEN;
+
+            for (i, s) in spectrum.iter().enumerate() {
+                if i == expected_index { assert_approx_eq!((FFT_LEN * FFT_LEN) as f32, s, 0.1); }
+                else { assert!(*s < 1e-3); }
+            }
+        }
+
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 2)), analyzer.analyze_complex(&[Complex::zero(); 2]).map(|_| ()));
+    }
+
+    #[test]
+    fn test_cross_spectrum() {
+        use std::f32::consts::PI;
+
+        const FFT_LEN: usize = 256;
+        const DELAY: usize = 3;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        // A scaled copy of broadband noise, circularly delayed so that the delay is exact within the frame.
+        let x = TestUtil::generate_noise_samples(FFT_LEN);
+        let y: Vec<_> = (0..FFT_LEN).map(|n| 0.5 * x[(n + FFT_LEN - DELAY) % FFT_LEN]).collect();
+
+        let cross = analyzer.cross_spectrum(&x, &y).unwrap();
+        assert_eq!(FFT_LEN, cross.len());
+
+        // The phase changes linearly with frequency, by the delay in each bin.
+        let expected_step = -2.0 * PI * DELAY as f32 / FFT_LEN as f32;
+        for k in 1..(FFT_LEN / 2) {
+            let step = (cross[k + 1] * cross[k].conj()).arg();
+            assert_approx_eq!(expected_step, step, 1e-3);
+        }
+
+        // The magnitude is scaled along with the copy.
+        let (x_spectrum, _) = analyzer.analyze(&SampleBuffer::from(x.clone())).unwrap();
+        for (c, p) in cross.iter().zip(x_spectrum) {
+            assert!((c.norm() - 0.5 * p).abs() <= 1e-3 * p.max(1.0));
+        }
+
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 2)), analyzer.cross_spectrum(&x, &[0.0; 2]));
+    }
+
+    #[test]
+    fn test_time_crest_factor() {
+        use crate::wave::WaveGen;
+        use crate::wave::WaveFunction;
+
+        const FFT_LEN: usize = 1024;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Rectangular);
+
+        // Only the tail of the buffer is considered, so the leading silence is ignored.
+        let mut samples = vec![0.0; 100];
+        samples.extend(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 441.0, FFT_LEN));
+        let produced = analyzer.time_crest_factor(&SampleBuffer::from(samples)).unwrap();
+        assert_approx_eq!(2.0f32.sqrt(), produced, 1e-2);
+
+        let square: Vec<_> = WaveGen::new(WaveFunction::Square, SAMPLES_PER_PERIOD, 441.0).take(FFT_LEN).collect();
+        let produced = analyzer.time_crest_factor(&SampleBuffer::from(square)).unwrap();
+        assert_approx_eq!(1.0, produced);
+
+        assert_eq!(0.0, analyzer.time_crest_factor(&SampleBuffer::new(FFT_LEN)).unwrap());
+        assert_eq!(Err(Error::TooFewSamples(FFT_LEN, 4)), analyzer.time_crest_factor(&SampleBuffer::new(4)));
+    }
+
+    #[test]
+    fn test_amplitude_normalization() {
+        const FFT_LEN: usize = 1024;
+        const BIN: usize = 32;
+
+        for window_kind in [WindowKind::Rectangular, WindowKind::Hanning, WindowKind::Blackman] {
+            let mut analyzer = Analyzer::new(FFT_LEN, window_kind).with_amplitude_normalization(true);
+
+            // A full-scale sine centered on a bin.
+            let frequency = analyzer.bin_frequency(BIN, SAMPLES_PER_PERIOD).unwrap();
+            let full_scale: Vec<_> =
+                TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, FFT_LEN)
+                .into_iter()
+                .map(|s| s * 4.0)
+                .collect()
+            ;
+
+            let (spectrum, _) = analyzer.analyze(&SampleBuffer::from(full_scale)).unwrap();
+
+            assert_approx_eq!(1.0, spectrum[BIN].sqrt(), 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_coherent_gain_correction() {
+        const FFT_LEN: usize = 1024;
+        const BIN: usize = 32;
+
+        let peak_power = |window_kind: WindowKind, normalize: bool| {
+            let mut analyzer = Analyzer::new(FFT_LEN, window_kind).with_amplitude_normalization(normalize);
+
+            let frequency = analyzer.bin_frequency(BIN, SAMPLES_PER_PERIOD).unwrap();
+            let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, frequency, FFT_LEN);
+
+            analyzer.analyze(&SampleBuffer::from(samples)).unwrap().0[BIN]
+        };
+
+        // Tapering loses power, which the correction restores.
+        let rectangular = peak_power(WindowKind::Rectangular, true);
+        let hanning = peak_power(WindowKind::Hanning, true);
+        assert_approx_eq!(1.0, hanning / rectangular, 1e-3);
+
+        let rectangular = peak_power(WindowKind::Rectangular, false);
+        let hanning = peak_power(WindowKind::Hanning, false);
+        assert!(hanning / rectangular < 0.3);
+
+        let gain = WindowKind::Hanning.coherent_gain(FFT_LEN) as SignalStrength;
+        assert_approx_eq!(1.0, hanning / gain.powi(2) / rectangular, 1e-3);
+    }
+
+    #[test]
+    fn test_with_backend() {
+        use std::sync::Mutex;
+
+        // Records the frames it is given, and produces a silent spectrum.
+        struct MockBackend(usize, Mutex<Vec<Vec<Complex<Sample>>>>);
+
+        impl SpectrumBackend for MockBackend {
+            fn len(&self) -> usize {
+                self.0
+            }
+
+            fn forward(&self, input: &mut [Complex<Sample>], output: &mut [Complex<Sample>]) {
+                self.1.lock().unwrap().push(input.to_vec());
+                for o in output.iter_mut() {
+                    *o = Complex::zero();
+                }
+            }
+        }
+
+        const FFT_LEN: usize = 16;
+
+        let backend = Arc::new(MockBackend(FFT_LEN, Mutex::new(Vec::new())));
+        let mut analyzer = Analyzer::with_backend(backend.clone(), WindowKind::Blackman);
+
+        assert_eq!(FFT_LEN, analyzer.len());
+
+        let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let (spectrum, _) = analyzer.analyze(&SampleBuffer::from(samples.clone())).unwrap();
+        assert_eq!(vec![0.0; FFT_LEN], spectrum);
+
+        // Called once per channel, with the windowed frame.
+        let frames = backend.1.lock().unwrap();
+        assert_eq!(2, frames.len());
+
+        let window = WindowKind::Blackman.generate(FFT_LEN);
+        for frame in frames.iter() {
+            for ((f, s), w) in frame.iter().zip(&samples).zip(&window) {
+                assert_eq!(Complex::new(s * *w as f32, 0.0), *f);
+            }
+        }
+    }
+
+    #[test]
+    fn test_silence_gate() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::atomic::Ordering;
+
+        // Counts how many transforms are run.
+        struct CountingBackend(RustFftBackend, AtomicUsize);
+
+        impl SpectrumBackend for CountingBackend {
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            fn forward(&self, input: &mut [Complex<Sample>], output: &mut [Complex<Sample>]) {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                self.0.forward(input, output);
+            }
+        }
+
+        const FFT_LEN: usize = 256;
+
+        let backend = Arc::new(CountingBackend(RustFftBackend::new(FFT_LEN), AtomicUsize::new(0)));
+        let mut analyzer = Analyzer::with_backend(backend.clone(), WindowKind::Hanning).with_silence_gate(0.01);
+        assert_eq!(Some(0.01), analyzer.silence_threshold());
+
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        // Quiet frames are zeroed without running the FFT.
+        let quiet: Vec<_> = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN).into_iter().map(|s| s * 0.01).collect();
+        let produced = analyzer.process(&SampleBuffer::from(quiet), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(vec![0.0; buckets.len()], produced);
+        assert_eq!(0, backend.1.load(Ordering::SeqCst));
+
+        // Louder frames are analyzed as usual.
+        let loud = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        let produced = analyzer.process(&SampleBuffer::from(loud.clone()), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(2, backend.1.load(Ordering::SeqCst));
+
+        let expected = Analyzer::new(FFT_LEN, WindowKind::Hanning).process(&SampleBuffer::from(loud), &buckets, SAMPLES_PER_PERIOD).unwrap();
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_non_finite_policy() {
+        const FFT_LEN: usize = 64;
+
+        let mut samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+        samples[10] = Sample::NAN;
+        samples[20] = Sample::INFINITY;
+        let samples = SampleBuffer::from(samples);
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let (spectrum_l, _) = analyzer.analyze(&samples).unwrap();
+        assert!(spectrum_l.iter().any(|p| !p.is_finite()));
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning).with_non_finite_policy(NonFinitePolicy::Sanitize);
+        let (spectrum_l, spectrum_r) = analyzer.analyze(&samples).unwrap();
+        assert!(spectrum_l.iter().chain(spectrum_r).all(|p| p.is_finite()));
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning).with_non_finite_policy(NonFinitePolicy::Strict);
+        assert_eq!(Err(Error::NonFiniteInput), analyzer.analyze(&samples).map(|_| ()));
+    }
+
+    #[test]
+    fn test_pre_emphasis() {
+        const FFT_LEN: usize = 1024;
+        const LOW: Frequency = 200.0;
+        const HIGH: Frequency = 10000.0;
+
+        let low = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, LOW, FFT_LEN);
+        let high = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, HIGH, FFT_LEN);
+        let samples = SampleBuffer::from(low.iter().zip(&high).map(|(l, h)| l + h).collect::<Vec<_>>());
+
+        let mut plain = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let mut emphasized = Analyzer::new(FFT_LEN, WindowKind::Hanning).with_pre_emphasis(0.97);
+
+        let fft_bin_size = plain.fft_bin_size(SAMPLES_PER_PERIOD).unwrap();
+        let low_bin = (LOW / fft_bin_size).round() as usize;
+        let high_bin = (HIGH / fft_bin_size).round() as usize;
+
+        let spectrum = plain.analyze(&samples).unwrap().0;
+        let plain_ratio = spectrum[high_bin] / spectrum[low_bin];
+
+        let spectrum = emphasized.analyze(&samples).unwrap().0;
+        let emphasized_ratio = spectrum[high_bin] / spectrum[low_bin];
+
+        // The high tone is boosted relative to the low one.
+        assert!(emphasized_ratio > plain_ratio * 100.0);
+
+        // A zero coefficient leaves the spectrum unchanged.
+        let mut disabled = Analyzer::new(FFT_LEN, WindowKind::Hanning).with_pre_emphasis(0.0);
+        assert_eq!(plain.analyze(&samples).unwrap().0, disabled.analyze(&samples).unwrap().0);
+    }
+
+    #[test]
+    fn test_window_coefficients() {
+        const FFT_LEN: usize = 64;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let produced = analyzer.window_coefficients();
+
+        assert_eq!(analyzer.len(), produced.len());
+        assert_eq!(0.0, produced[0]);
+        assert_eq!(0.0, produced[FFT_LEN - 1]);
+
+        let expected: Vec<_> = WindowKind::Hanning.generate(FFT_LEN).into_iter().map(|w| w as f32).collect();
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_apply_window() {
+        const FFT_LEN: usize = 64;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Blackman);
+
+        let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN);
+
+        let expected: Vec<_> =
+            samples.iter().zip(WindowKind::Blackman.generate(FFT_LEN))
+            .map(|(s, w)| s * w as Sample)
+            .collect()
+        ;
+
+        let mut produced = samples.clone();
+        analyzer.apply_window(&mut produced).unwrap();
+        assert_eq!(expected, produced);
+
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 4)), analyzer.apply_window(&mut [0.0; 4]));
+    }
+
+    #[test]
+    fn test_analyze_repeated() {
+        const FFT_LEN: usize = 256;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let wave = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+        let noise = SampleBuffer::from(TestUtil::generate_noise_samples(FFT_LEN));
+
+        let (expected_l, expected_r) = analyzer.analyze(&wave).map(|(l, r)| (l.to_vec(), r.to_vec())).unwrap();
+
+        // Nothing from an earlier call leaks into a later one through the reused buffers.
+        for _ in 0..3 {
+            analyzer.analyze(&noise).unwrap();
+            assert_eq!((expected_l.as_slice(), expected_r.as_slice()), analyzer.analyze(&wave).unwrap());
+        }
+
+        // A clone produces the same results with its own buffers.
+        let mut cloned = analyzer.clone();
+        analyzer.analyze(&noise).unwrap();
+        assert_eq!((expected_l.as_slice(), expected_r.as_slice()), cloned.analyze(&wave).unwrap());
+        assert_eq!((expected_l.as_slice(), expected_r.as_slice()), Analyzer::new(FFT_LEN, WindowKind::Hanning).analyze(&wave).unwrap());
+    }
+
+    #[test]
+    fn test_new_checked() {
+        const MAX_LEN: usize = 4096;
+
+        assert_eq!(MAX_LEN, Analyzer::new_checked(MAX_LEN, WindowKind::Hanning, MAX_LEN).unwrap().len());
+        assert_eq!(Err(Error::FftTooLarge(1 << 30, MAX_LEN)), Analyzer::new_checked(1 << 30, WindowKind::Hanning, MAX_LEN).map(|_| ()));
+    }
+
+    #[test]
+    fn test_analyze_checked() {
+        const FFT_LEN: usize = 64;
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        assert_eq!(None, analyzer.expects_sampling_rate());
+        assert!(analyzer.analyze_checked(&samples, 48000).is_ok());
+
+        let mut analyzer = analyzer.with_sampling_rate(SAMPLES_PER_PERIOD);
+        assert_eq!(Some(SAMPLES_PER_PERIOD), analyzer.expects_sampling_rate());
+
+        let expected = analyzer.analyze(&samples).unwrap().0.to_vec();
+        assert_eq!(expected.as_slice(), analyzer.analyze_checked(&samples, SAMPLES_PER_PERIOD).unwrap().0);
+
+        assert_eq!(Err(Error::SampleRateMismatch(SAMPLES_PER_PERIOD, 48000)), analyzer.analyze_checked(&samples, 48000).map(|_| ()));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "sampling rate does not match")]
+    fn test_sampling_rate_debug_assert() {
+        let analyzer = Analyzer::new(64, WindowKind::Hanning).with_sampling_rate(SAMPLES_PER_PERIOD);
+        let _ = analyzer.fft_bin_size(48000);
+    }
+
+    #[test]
+    fn test_is_compatible_with() {
+        const FFT_LEN: usize = 64;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let undersized = SampleBuffer::new(FFT_LEN - 1);
+        assert!(!analyzer.is_compatible_with(&undersized));
+        assert!(analyzer.analyze(&undersized).is_err());
+
+        let buffer = SampleBuffer::new(FFT_LEN);
+        assert!(analyzer.is_compatible_with(&buffer));
+        assert!(analyzer.analyze(&buffer).is_ok());
+
+        // A longer buffer is analyzed by its most recent frame.
+        let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, 4 * FFT_LEN);
+        let oversized = SampleBuffer::from(samples.clone());
+        assert!(analyzer.is_compatible_with(&oversized));
+
+        let expected = analyzer.analyze(&SampleBuffer::from(samples[3 * FFT_LEN..].to_vec())).unwrap().0.to_vec();
+        assert_eq!(expected, analyzer.analyze(&oversized).unwrap().0);
+    }
+
+    #[test]
+    fn test_default() {
+        let analyzer = Analyzer::default();
+
+        assert_eq!(2048, analyzer.len());
     }
 }
@@ -0,0 +1,42 @@
+//! Abstraction over the forward FFT used by the analyzer.
+
+use std::sync::Arc;
+
+use rustfft::FFT;
+use rustfft::FFTplanner;
+use rustfft::num_complex::Complex;
+
+use crate::sample::Sample;
+
+/// A forward transform of a fixed length, used to turn a windowed frame into a spectrum.
+pub trait SpectrumBackend: Send + Sync {
+    /// Length of the frames this backend transforms.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Transforms `input` into `output`, both of which are `len()` long.
+    /// The contents of `input` may be overwritten.
+    fn forward(&self, input: &mut [Complex<Sample>], output: &mut [Complex<Sample>]);
+}
+
+/// The default backend, using a planned `rustfft` FFT.
+pub struct RustFftBackend(Arc<dyn FFT<Sample>>);
+
+impl RustFftBackend {
+    pub fn new(len: usize) -> Self {
+        Self(FFTplanner::new(false).plan_fft(len))
+    }
+}
+
+impl SpectrumBackend for RustFftBackend {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn forward(&self, input: &mut [Complex<Sample>], output: &mut [Complex<Sample>]) {
+        self.0.process(input, output);
+    }
+}
@@ -73,3 +73,60 @@ impl Detector {
         false
     }
 }
+
+/// Estimates the tempo in beats per minute from an onset detection function sampled at `frame_rate` frames per second,
+/// such as one produced by `Analyzer::onset_function`.
+/// The tempo is the lag with the strongest autocorrelation among those within `bpm_range`.
+/// Returns `None` if no lag in the range fits in the onset function, or if it has no periodicity at all.
+pub fn estimate_tempo(onset_function: &[f32], frame_rate: f32, bpm_range: (f32, f32)) -> Option<f32> {
+    let (min_bpm, max_bpm) = bpm_range;
+
+    if !(frame_rate > 0.0 && min_bpm > 0.0 && min_bpm < max_bpm) { return None }
+
+    let n = onset_function.len();
+    let mean = onset_function.iter().sum::<f32>() / n.max(1) as f32;
+    let centered: Vec<_> = onset_function.iter().map(|o| o - mean).collect();
+
+    // A tempo of `bpm` repeats every `60 * frame_rate / bpm` frames.
+    let min_lag = ((60.0 * frame_rate / max_bpm).ceil() as usize).max(1);
+    let max_lag = ((60.0 * frame_rate / min_bpm).floor() as usize).min(n.saturating_sub(1));
+
+    if min_lag > max_lag { return None }
+
+    // Unnormalized, so that shorter lags win ties with their multiples.
+    let autocorrelation = |lag: usize| -> f32 { centered.iter().zip(&centered[lag..]).map(|(a, b)| a * b).sum() };
+
+    let (lag, strength) =
+        (min_lag..=max_lag)
+        .map(|lag| (lag, autocorrelation(lag)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?
+    ;
+
+    if !(strength > 0.0) { return None }
+
+    Some(60.0 * frame_rate / lag as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tempo() {
+        const FRAME_RATE: f32 = 100.0;
+
+        // Clicks every half second is 120 BPM, with a little noise in between.
+        let onset_function: Vec<_> = (0..1000).map(|i| if i % 50 == 0 { 1.0 } else { (i % 7) as f32 * 0.01 }).collect();
+
+        let produced = estimate_tempo(&onset_function, FRAME_RATE, (60.0, 180.0)).unwrap();
+        assert_approx_eq!(120.0, produced, 1.0);
+
+        // The same clicks read as a multiple of the tempo when it is outside of the range.
+        let produced = estimate_tempo(&onset_function, FRAME_RATE, (40.0, 100.0)).unwrap();
+        assert_approx_eq!(60.0, produced, 1.0);
+
+        assert_eq!(None, estimate_tempo(&[0.0; 1000], FRAME_RATE, (60.0, 180.0)));
+        assert_eq!(None, estimate_tempo(&onset_function[..10], FRAME_RATE, (60.0, 180.0)));
+        assert_eq!(None, estimate_tempo(&onset_function, FRAME_RATE, (180.0, 60.0)));
+    }
+}
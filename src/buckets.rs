@@ -3,11 +3,22 @@ use std::cmp::Ordering;
 use crate::Error;
 use crate::types::Frequency;
 use crate::types::SignalStrength;
+use crate::note::NoteName;
+use crate::weighting;
+use crate::conversion;
+
+// Largest deviation reported, in decibels, so that empty buckets stay finite.
+const DEVIATION_LIMIT_DB: f32 = 100.0;
 
 #[derive(Clone)]
-pub struct Buckets(Vec<(Frequency, Frequency)>);
+pub struct Buckets(
+    Vec<(Frequency, Frequency)>,
+    // Number of lowest valid FFT bins merged into the lowest band.
+    usize,
+);
 
 impl Buckets {
+    /// Creates bands spaced logarithmically between the cutoffs, so that each covers the same frequency ratio.
     // Inspired by https://stackoverflow.com/a/10462090/388739
     pub fn new(lower_cutoff: Frequency, upper_cutoff: Frequency, num_bands: usize) -> Result<Self, Error> {
         // Check invariants.
@@ -35,7 +46,76 @@ impl Buckets {
             curr_lower_limit = curr_upper_limit;
         }
 
-        Ok(Self(partitions))
+        Ok(Self(partitions, 0))
+    }
+
+    /// Creates bands of equal width between the cutoffs.
+    pub fn new_linear(lower_cutoff: Frequency, upper_cutoff: Frequency, num_bands: usize) -> Result<Self, Error> {
+        Self::new_blended(lower_cutoff, upper_cutoff, num_bands, 0.0)
+    }
+
+    /// Creates bands with edges interpolated between linear and logarithmic spacing.
+    /// A `blend` of zero is the same as `new_linear`, and a `blend` of one is the same as `new`.
+    pub fn new_blended(lower_cutoff: Frequency, upper_cutoff: Frequency, num_bands: usize, blend: f32) -> Result<Self, Error> {
+        // Check invariants.
+        if !(upper_cutoff > 0.0) { Err(Error::UpperCutoff)? }
+        if !(lower_cutoff > 0.0) { Err(Error::LowerCutoff)? }
+        if !(lower_cutoff < upper_cutoff) { Err(Error::CutoffOrder)? }
+        if !(0.0..=1.0).contains(&blend) { Err(Error::BlendFactor)? }
+
+        let edge = |i: usize| {
+            if i == num_bands { return upper_cutoff }
+
+            let t = i as f32 / num_bands as f32;
+            let linear = lower_cutoff + (upper_cutoff - lower_cutoff) * t;
+            let logarithmic = lower_cutoff * (upper_cutoff / lower_cutoff).powf(t);
+
+            (1.0 - blend) * linear + blend * logarithmic
+        };
+
+        let partitions = (0..num_bands).map(|i| (edge(i), edge(i + 1))).collect();
+
+        Ok(Self(partitions, 0))
+    }
+
+    /// Creates the 31 ISO 266 third-octave bands, with base-ten center frequencies from 20 Hz to 20 kHz around 1 kHz.
+    pub fn new_third_octave() -> Self {
+        let edge = |k: i32| 1000.0 * (10.0 as Frequency).powf((2 * k - 1) as Frequency / 20.0);
+
+        Self((-17..=13).map(|k| (edge(k), edge(k + 1))).collect(), 0)
+    }
+
+    /// Creates buckets from explicit bands, which must be increasing and contiguous.
+    pub fn from_bands(bands: Vec<(Frequency, Frequency)>) -> Result<Self, Error> {
+        let buckets = Self(bands, 0);
+        buckets.validate()?;
+        Ok(buckets)
+    }
+
+    /// Checks that each band's edges are increasing,
+    /// and that each band starts exactly where the previous one ends, without any gap or overlap.
+    /// Returns the first violation found, along with the index of the offending band.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (i, (lo, hi)) in self.0.iter().enumerate() {
+            if !(lo < hi) { Err(Error::BandOrder(i))? }
+        }
+
+        for (i, pair) in self.0.windows(2).enumerate() {
+            let (_, prev_hi) = pair[0];
+            let (next_lo, _) = pair[1];
+
+            if prev_hi < next_lo { Err(Error::BandGap(i + 1))? }
+            if prev_hi > next_lo { Err(Error::BandOverlap(i + 1))? }
+        }
+
+        Ok(())
+    }
+
+    /// Merges the lowest `num_bins` valid FFT bins into a single reading when bucketizing, added to the lowest band.
+    /// This keeps the noisy near-DC bins of a large FFT from spreading across the low bands.
+    pub fn with_merged_low_bins(mut self, num_bins: usize) -> Self {
+        self.1 = num_bins;
+        self
     }
 
     #[inline]
@@ -54,12 +134,65 @@ impl Buckets {
         }).ok()
     }
 
+    /// Finds the band containing the given note, tuned so that A4 is `reference_hz`.
+    pub fn bucket_for_note(&self, note: NoteName, octave: i32, reference_hz: Frequency) -> Option<usize> {
+        self.locate(note.frequency(octave, reference_hz))
+    }
+
     #[inline]
     pub fn bands(&self) -> &[(Frequency, Frequency)] {
         self.0.as_slice()
     }
 
     pub fn bucketize(&self, spectrum: &[SignalStrength], sampling_rate: usize) -> Result<Vec<SignalStrength>, Error> {
+        self.bucketize_with_dc(spectrum, sampling_rate, false)
+    }
+
+    /// Same as `bucketize`, but converts each bucket to decibels relative to `reference` power.
+    /// Anything below `floor_db`, including silent buckets, is clamped to `floor_db`.
+    pub fn bucketize_db(&self, spectrum: &[SignalStrength], sampling_rate: usize, reference: SignalStrength, floor_db: f32) -> Result<Vec<f32>, Error> {
+        if !(reference > 0.0) { Err(Error::ReferenceLevel)? }
+        if !(floor_db < 0.0) { Err(Error::DecibelFloor)? }
+
+        let relative: Vec<_> = self.bucketize(spectrum, sampling_rate)?.into_iter().map(|b| b / reference).collect();
+
+        Ok(conversion::power_to_db(&relative, floor_db))
+    }
+
+    /// Same as `bucketize`, but normalizes against the given percentile of the buckets rather than the loudest one,
+    /// so that a single loud bucket does not crush the rest. Buckets above the percentile are clamped to 1.
+    /// If the percentile is silent, the loudest bucket is used instead.
+    pub fn bucketize_robust_normalized(&self, spectrum: &[SignalStrength], sampling_rate: usize, percentile: f32) -> Result<Vec<SignalStrength>, Error> {
+        if !(0.0..=100.0).contains(&percentile) { Err(Error::Percentile)? }
+
+        let mut bucketized = self.bucketize(spectrum, sampling_rate)?;
+
+        let mut sorted = bucketized.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        // Interpolates between the two closest ranks.
+        let reference = match sorted.len() {
+            0 => 0.0,
+            n => {
+                let rank = percentile / 100.0 * (n - 1) as f32;
+                let (lower, upper) = (sorted[rank.floor() as usize], sorted[rank.ceil() as usize]);
+                lower + (rank - rank.floor()) * (upper - lower)
+            },
+        };
+
+        let reference = if reference > 0.0 { reference } else { sorted.last().cloned().unwrap_or(0.0) };
+
+        if reference > 0.0 {
+            for b in bucketized.iter_mut() {
+                *b = (*b / reference).min(1.0);
+            }
+        }
+
+        Ok(bucketized)
+    }
+
+    /// Same as `bucketize`, but optionally adds the DC bin's power to the lowest band.
+    pub fn bucketize_with_dc(&self, spectrum: &[SignalStrength], sampling_rate: usize, include_dc: bool) -> Result<Vec<SignalStrength>, Error> {
         if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
 
         let mut bucketized = vec![0.0f32; self.len()];
@@ -73,7 +206,13 @@ impl Buckets {
 
                 let fft_bin_size = sampling_rate as f32 / n as f32;
 
-                for i in valid_fft_indices {
+                let num_merged = self.1.min(n / 2);
+
+                if let Some(lowest) = bucketized.first_mut() {
+                    *lowest += spectrum[1..=num_merged].iter().sum::<SignalStrength>();
+                }
+
+                for i in valid_fft_indices.skip(num_merged) {
                     let freq_bin = fft_bin_size * i as f32;
 
                     // Where does this frequency bin fall in the buckets?
@@ -81,11 +220,244 @@ impl Buckets {
                         bucketized[band_index] += spectrum[i];
                     }
                 }
+
+                // The zero frequency falls below every band, so it goes into the lowest one.
+                if include_dc {
+                    if let Some(lowest) = bucketized.first_mut() {
+                        *lowest += spectrum[0];
+                    }
+                }
             },
         };
 
         Ok(bucketized)
     }
+
+    /// Same as `bucketize`, but only calculates the bands at the given indices, in the order given.
+    pub fn bucketize_subset(&self, spectrum: &[SignalStrength], sampling_rate: usize, indices: &[usize]) -> Result<Vec<SignalStrength>, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+        let n = spectrum.len();
+        let fft_bin_size = sampling_rate as f32 / n as f32;
+
+        let mut bucketized = Vec::with_capacity(indices.len());
+
+        for &band_index in indices {
+            let (lo, hi) = *self.0.get(band_index).ok_or(Error::BucketIndex(band_index, self.len()))?;
+
+            // Only visit the bins near this band, using the same valid range and bounds check as `bucketize`.
+            // Merged bins are skipped here, and only counted towards the lowest band.
+            let num_merged = self.1.min(n / 2);
+            let first = ((lo / fft_bin_size).floor() as usize).max(1 + num_merged);
+            let last = ((hi / fft_bin_size).ceil() as usize).min(n / 2);
+
+            let mut power: SignalStrength =
+                (first..=last)
+                .filter(|&i| {
+                    let freq_bin = fft_bin_size * i as f32;
+                    lo <= freq_bin && freq_bin < hi
+                })
+                .map(|i| spectrum[i])
+                .sum()
+            ;
+
+            if band_index == 0 && n > 0 { power += spectrum[1..=num_merged].iter().sum::<SignalStrength>(); }
+
+            bucketized.push(power);
+        }
+
+        Ok(bucketized)
+    }
+
+    /// Reports how well the bands cover a spectrum of the given length.
+    /// Returns the fraction of the range from zero to Nyquist covered by some band,
+    /// and the fraction of valid FFT bins that are assigned to some band when bucketizing.
+    pub fn coverage(&self, fft_len: usize, sampling_rate: usize) -> Result<(f32, f32), Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+        let nyquist = sampling_rate as f32 / 2.0;
+
+        let covered: f32 =
+            self.0.iter()
+            .map(|(lo, hi)| (hi.min(nyquist) - lo.min(nyquist)).max(0.0))
+            .sum()
+        ;
+
+        let num_valid_bins = fft_len / 2;
+
+        let bin_fraction = match num_valid_bins {
+            0 => 0.0,
+            n => {
+                let fft_bin_size = sampling_rate as f32 / fft_len as f32;
+                let num_assigned = (1..=n).filter(|&i| self.locate(fft_bin_size * i as f32).is_some()).count();
+                num_assigned as f32 / n as f32
+            },
+        };
+
+        Ok((covered / nyquist, bin_fraction))
+    }
+
+    /// Finds the bands that no valid FFT bin falls into for a given FFT length and sampling rate,
+    /// which always output zero. A larger FFT or fewer bands will fill them.
+    pub fn empty_buckets(&self, fft_len: usize, sampling_rate: usize) -> Result<Vec<usize>, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+        let mut num_bins = vec![0usize; self.len()];
+
+        if fft_len > 0 {
+            let fft_bin_size = sampling_rate as f32 / fft_len as f32;
+            let num_merged = self.1.min(fft_len / 2);
+
+            if let Some(lowest) = num_bins.first_mut() { *lowest += num_merged; }
+
+            // Same valid range and assignment as `bucketize`.
+            for i in (1..=(fft_len / 2)).skip(num_merged) {
+                if let Some(band_index) = self.locate(fft_bin_size * i as f32) {
+                    num_bins[band_index] += 1;
+                }
+            }
+        }
+
+        Ok(num_bins.iter().enumerate().filter(|(_, &n)| n == 0).map(|(i, _)| i).collect())
+    }
+
+    /// Weights bucketized output by the ISO 226 equal-loudness contour at `phon`,
+    /// evaluated at the geometric center of each band.
+    pub fn apply_equal_loudness(&self, buckets: &[SignalStrength], phon: f32) -> Result<Vec<SignalStrength>, Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+        if !(0.0..=90.0).contains(&phon) { Err(Error::PhonLevel)? }
+
+        let weighted =
+            self.0.iter().zip(buckets)
+            .map(|((lo, hi), b)| b * weighting::equal_loudness_gain((lo * hi).sqrt(), phon))
+            .collect()
+        ;
+
+        Ok(weighted)
+    }
+
+    /// Limits bucketized output to a ceiling, leaving values at or below it untouched.
+    pub fn clamp_buckets(&self, buckets: &mut [SignalStrength], max: SignalStrength) -> Result<(), Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+
+        for b in buckets.iter_mut() {
+            *b = b.min(max);
+        }
+
+        Ok(())
+    }
+
+    /// Quantizes bucketized output to `levels` discrete steps, after normalizing by the loudest bucket.
+    /// The loudest bucket maps to `levels - 1`, and silence maps to zero.
+    pub fn quantize_buckets(&self, buckets: &[SignalStrength], levels: usize) -> Result<Vec<u8>, Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+        if !(1..=256).contains(&levels) { Err(Error::QuantizationLevels(levels))? }
+
+        let max = buckets.iter().cloned().fold(0.0, SignalStrength::max);
+        let top = (levels - 1) as SignalStrength;
+
+        let quantized =
+            buckets
+            .iter()
+            .map(|b| if max > 0.0 { (b.max(0.0) / max * top).round() as u8 } else { 0 })
+            .collect()
+        ;
+
+        Ok(quantized)
+    }
+
+    /// Calculates the magnitude-weighted mean index of bucketized output, for placing a marker between bars.
+    /// Silence has a centroid of zero.
+    pub fn bucket_centroid(&self, buckets: &[SignalStrength]) -> Result<f32, Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+
+        let (weighted_sum, total) =
+            buckets.iter().enumerate()
+            .fold((0.0, 0.0), |(ws, t), (i, b)| (ws + i as f32 * b, t + b))
+        ;
+
+        if total > 0.0 { Ok(weighted_sum / total) }
+        else { Ok(0.0) }
+    }
+
+    /// Describes the loudest bucket of bucketized output for a readout, such as "low-mid (250–500 Hz)".
+    /// The name comes from the band's geometric center, ranging through sub-bass, bass, low-mid, mid, high-mid, treble, and air.
+    /// Silence has no dominant band.
+    pub fn dominant_band_label(&self, buckets: &[SignalStrength]) -> Result<Option<String>, Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+
+        // Upper limit of each named range, with anything above the last being air.
+        const NAMES: [(Frequency, &str); 6] = [
+            (60.0, "sub-bass"),
+            (250.0, "bass"),
+            (500.0, "low-mid"),
+            (2000.0, "mid"),
+            (4000.0, "high-mid"),
+            (10000.0, "treble"),
+        ];
+
+        let loudest =
+            buckets.iter().enumerate()
+            .filter(|(_, b)| **b > 0.0)
+            .fold(None, |best: Option<(usize, SignalStrength)>, (i, &b)| match best {
+                Some((_, max)) if max >= b => best,
+                _ => Some((i, b)),
+            })
+        ;
+
+        let label = loudest.map(|(i, _)| {
+            let (lo, hi) = self.0[i];
+            let center = (lo * hi).sqrt();
+
+            let name = NAMES.iter().find(|(limit, _)| center < *limit).map(|(_, name)| *name).unwrap_or("air");
+
+            format!("{} ({:.0}–{:.0} Hz)", name, lo, hi)
+        });
+
+        Ok(label)
+    }
+
+    /// Formats the geometric center frequency of each band as a comma-separated CSV header, for logging bucketized output.
+    pub fn csv_header(&self) -> String {
+        self.0.iter().map(|(lo, hi)| (lo * hi).sqrt().to_string()).collect::<Vec<_>>().join(",")
+    }
+
+    /// Formats one frame of bucketized output as a comma-separated CSV row, with columns matching `csv_header`.
+    pub fn format_csv_row(&self, buckets: &[SignalStrength]) -> Result<String, Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+
+        Ok(buckets.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(","))
+    }
+
+    /// Calculates the per-bucket difference in decibels between bucketized output and a reference.
+    /// Differences are clamped to +/- 100 dB, and buckets that are empty in both are considered equal.
+    pub fn deviation_from(&self, buckets: &[SignalStrength], reference: &[SignalStrength]) -> Result<Vec<f32>, Error> {
+        if self.len() != buckets.len() { Err(Error::BucketsLength(self.len(), buckets.len()))? }
+        if self.len() != reference.len() { Err(Error::BucketsLength(self.len(), reference.len()))? }
+
+        let deviation =
+            buckets.iter().zip(reference)
+            .map(|(b, r)| {
+                if *b == 0.0 && *r == 0.0 { 0.0 }
+                else { (10.0 * (b / r).log10()).clamp(-DEVIATION_LIMIT_DB, DEVIATION_LIMIT_DB) }
+            })
+            .collect()
+        ;
+
+        Ok(deviation)
+    }
+
+    /// Calculates the per-bucket level difference in decibels between the left and right channels.
+    /// Positive values mean the left channel is louder.
+    pub fn interchannel_level_difference(&self, left_buckets: &[SignalStrength], right_buckets: &[SignalStrength]) -> Result<Vec<f32>, Error> {
+        self.deviation_from(left_buckets, right_buckets)
+    }
+}
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Buckets::new(20.0, 20000.0, 24).expect("default cutoffs are valid")
+    }
 }
 
 #[cfg(test)]
@@ -144,6 +516,94 @@ mod tests {
         assert_eq!(0, produced.len());
     }
 
+    #[test]
+    fn test_new_invalid() {
+        for &blend in &[0.0, 0.5, 1.0] {
+            assert_eq!(Err(Error::LowerCutoff), Buckets::new_blended(0.0, 20000.0, 8, blend).map(|_| ()));
+            assert_eq!(Err(Error::LowerCutoff), Buckets::new_blended(-20.0, 20000.0, 8, blend).map(|_| ()));
+            assert_eq!(Err(Error::UpperCutoff), Buckets::new_blended(20.0, 0.0, 8, blend).map(|_| ()));
+            assert_eq!(Err(Error::CutoffOrder), Buckets::new_blended(20000.0, 20.0, 8, blend).map(|_| ()));
+        }
+
+        // Logarithmic spacing cannot start from zero.
+        assert_eq!(Err(Error::LowerCutoff), Buckets::new(0.0, 20000.0, 8).map(|_| ()));
+        assert_eq!(Err(Error::LowerCutoff), Buckets::new(-20.0, 20000.0, 8).map(|_| ()));
+        assert_eq!(Err(Error::UpperCutoff), Buckets::new(20.0, 0.0, 8).map(|_| ()));
+        assert_eq!(Err(Error::CutoffOrder), Buckets::new(20000.0, 20.0, 8).map(|_| ()));
+    }
+
+    #[test]
+    fn test_validate() {
+        for buckets in &[Buckets::new(20.0, 20000.0, 24).unwrap(), Buckets::new_blended(20.0, 20000.0, 24, 0.5).unwrap()] {
+            assert_eq!(Ok(()), buckets.validate());
+        }
+
+        assert!(Buckets::from_bands(vec![(20.0, 40.0), (40.0, 80.0)]).is_ok());
+        assert!(Buckets::from_bands(vec![]).is_ok());
+
+        assert_eq!(Err(Error::BandOverlap(2)), Buckets::from_bands(vec![(20.0, 40.0), (40.0, 80.0), (60.0, 100.0)]).map(|_| ()));
+        assert_eq!(Err(Error::BandGap(1)), Buckets::from_bands(vec![(20.0, 40.0), (50.0, 80.0)]).map(|_| ()));
+        assert_eq!(Err(Error::BandOrder(1)), Buckets::from_bands(vec![(20.0, 40.0), (40.0, 40.0)]).map(|_| ()));
+    }
+
+    #[test]
+    fn test_new_third_octave() {
+        let buckets = Buckets::new_third_octave();
+        assert_eq!(31, buckets.len());
+        assert_eq!(Ok(()), buckets.validate());
+
+        let centers: Vec<_> = buckets.bands().iter().map(|(lo, hi)| (lo * hi).sqrt()).collect();
+        assert_approx_eq!(19.953, centers[0], 1e-3);
+        assert_approx_eq!(1000.0, centers[17], 1e-2);
+        assert_approx_eq!(19952.6, centers[30], 1e-1);
+
+        // Each band spans a tenth of a decade.
+        for (lo, hi) in buckets.bands() {
+            assert_approx_eq!(10.0f32.powf(0.1), hi / lo, 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_new_blended() {
+        let logarithmic = Buckets::new(20.0, 20000.0, 16).unwrap();
+        let produced = Buckets::new_blended(20.0, 20000.0, 16, 1.0).unwrap();
+
+        assert_eq!(logarithmic.len(), produced.len());
+        for (e, p) in logarithmic.bands().iter().zip(produced.bands()) {
+            assert_approx_eq!(e.0, p.0, 1e-2);
+            assert_approx_eq!(e.1, p.1, 1e-2);
+        }
+
+        let linear = Buckets::new_linear(20.0, 20000.0, 16).unwrap();
+        let produced = Buckets::new_blended(20.0, 20000.0, 16, 0.0).unwrap();
+
+        assert_eq!(linear.bands(), produced.bands());
+        for (lo, hi) in linear.bands() {
+            assert_approx_eq!(1248.75, hi - lo, 1e-2);
+        }
+
+        // Halfway blended bands are wider than logarithmic ones at the bottom, and narrower at the top.
+        let blended = Buckets::new_blended(20.0, 20000.0, 16, 0.5).unwrap();
+        let width = |b: &Buckets, i: usize| b.bands()[i].1 - b.bands()[i].0;
+
+        assert!(width(&blended, 0) > width(&logarithmic, 0));
+        assert!(width(&blended, 0) < width(&linear, 0));
+        assert!(width(&blended, 15) < width(&logarithmic, 15));
+        assert!(width(&blended, 15) > width(&linear, 15));
+
+        assert_eq!(0, Buckets::new_blended(20.0, 20000.0, 0, 0.5).unwrap().len());
+        assert!(Buckets::new_blended(20.0, 20000.0, 16, 1.5).is_err());
+    }
+
+    #[test]
+    fn test_default() {
+        let buckets = Buckets::default();
+
+        assert_eq!(24, buckets.len());
+        assert_approx_eq!(20.0, buckets.bands()[0].0);
+        assert_approx_eq!(20000.0, buckets.bands()[23].1);
+    }
+
     #[test]
     fn test_locate() {
         let partitions = Buckets::new(10.0, 22050.0, 16).unwrap();
@@ -163,6 +623,255 @@ mod tests {
             let produced = partitions.locate(input);
             assert_eq!(expected, produced);
         }
+
+        // Linear spacing locates the same way, with each band 1000 Hz wide.
+        let partitions = Buckets::new_linear(1000.0, 17000.0, 16).unwrap();
+
+        let inputs_and_expected = vec![
+            (999.9, None),
+            (1000.0, Some(0)),
+            (1999.9, Some(0)),
+            (2000.0, Some(1)),
+            (16999.9, Some(15)),
+            (17000.0, None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = partitions.locate(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_bucket_for_note() {
+        let buckets = Buckets::new(20.0, 20000.0, 24).unwrap();
+
+        let index = buckets.bucket_for_note(NoteName::A, 4, 440.0).unwrap();
+        let (lo, hi) = buckets.bands()[index];
+        assert!(lo <= 440.0 && 440.0 < hi);
+
+        // Notes outside of the cutoffs are not in any band.
+        assert_eq!(None, buckets.bucket_for_note(NoteName::C, -1, 440.0));
+    }
+
+    #[test]
+    fn test_clamp_buckets() {
+        let buckets = Buckets::new(20.0, 20000.0, 5).unwrap();
+
+        let mut produced = vec![0.0, 0.5, 1.0, 1.5, 100.0];
+        buckets.clamp_buckets(&mut produced, 1.0).unwrap();
+
+        assert_eq!(vec![0.0, 0.5, 1.0, 1.0, 1.0], produced);
+
+        assert_eq!(Err(Error::BucketsLength(5, 1)), buckets.clamp_buckets(&mut [0.0], 1.0));
+    }
+
+    #[test]
+    fn test_quantize_buckets() {
+        let buckets = Buckets::new(20.0, 20000.0, 5).unwrap();
+
+        let produced = buckets.quantize_buckets(&[0.0, 0.2, 0.5, 0.74, 2.0], 8).unwrap();
+        assert_eq!(vec![0, 1, 2, 3, 7], produced);
+
+        assert_eq!(vec![0, 0, 0, 0, 255], buckets.quantize_buckets(&[0.0, 0.0, 0.0, 0.0, 1.0], 256).unwrap());
+        assert_eq!(vec![0; 5], buckets.quantize_buckets(&[0.0; 5], 8).unwrap());
+        assert_eq!(vec![0; 5], buckets.quantize_buckets(&[0.0, 0.2, 0.5, 0.74, 2.0], 1).unwrap());
+
+        assert_eq!(Err(Error::BucketsLength(5, 1)), buckets.quantize_buckets(&[0.0], 8));
+        assert_eq!(Err(Error::QuantizationLevels(0)), buckets.quantize_buckets(&[0.0; 5], 0));
+        assert_eq!(Err(Error::QuantizationLevels(257)), buckets.quantize_buckets(&[0.0; 5], 257));
+    }
+
+    #[test]
+    fn test_bucket_centroid() {
+        let buckets = Buckets::new(20.0, 20000.0, 7).unwrap();
+
+        assert_eq!(3.0, buckets.bucket_centroid(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]).unwrap());
+        assert_approx_eq!(3.1, buckets.bucket_centroid(&[0.0, 0.0, 0.0, 0.9, 0.1, 0.0, 0.0]).unwrap());
+
+        // Balanced energy lands on the middle index.
+        assert_eq!(3.0, buckets.bucket_centroid(&[1.0; 7]).unwrap());
+        assert_eq!(3.0, buckets.bucket_centroid(&[0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.5]).unwrap());
+
+        assert_eq!(0.0, buckets.bucket_centroid(&[0.0; 7]).unwrap());
+        assert_eq!(Err(Error::BucketsLength(7, 1)), buckets.bucket_centroid(&[0.0]));
+    }
+
+    #[test]
+    fn test_dominant_band_label() {
+        let buckets = Buckets::from_bands(vec![(20.0, 60.0), (60.0, 250.0), (250.0, 500.0), (500.0, 2000.0), (2000.0, 20000.0)]).unwrap();
+
+        // Bass-heavy input.
+        assert_eq!(Some("bass (60–250 Hz)".to_string()), buckets.dominant_band_label(&[0.5, 1.0, 0.25, 0.1, 0.0]).unwrap());
+        assert_eq!(Some("low-mid (250–500 Hz)".to_string()), buckets.dominant_band_label(&[0.0, 0.0, 1.0, 0.0, 0.0]).unwrap());
+        assert_eq!(Some("treble (2000–20000 Hz)".to_string()), buckets.dominant_band_label(&[0.0, 0.0, 0.0, 0.0, 1.0]).unwrap());
+
+        // Ties go to the lowest band.
+        assert_eq!(Some("sub-bass (20–60 Hz)".to_string()), buckets.dominant_band_label(&[1.0; 5]).unwrap());
+
+        assert_eq!(None, buckets.dominant_band_label(&[0.0; 5]).unwrap());
+        assert_eq!(Err(Error::BucketsLength(5, 1)), buckets.dominant_band_label(&[0.0]));
+    }
+
+    #[test]
+    fn test_csv() {
+        let buckets = Buckets::from_bands(vec![(10.0, 40.0), (40.0, 160.0), (160.0, 640.0)]).unwrap();
+
+        let header = buckets.csv_header();
+        assert_eq!("20,80,320", header);
+        assert_eq!(buckets.len(), header.split(',').count());
+
+        let row = buckets.format_csv_row(&[0.5, 0.0, 1.25]).unwrap();
+        assert_eq!("0.5,0,1.25", row);
+        assert_eq!(buckets.len(), row.split(',').count());
+
+        assert_eq!(Err(Error::BucketsLength(3, 1)), buckets.format_csv_row(&[0.0]));
+    }
+
+    #[test]
+    fn test_bucketize_robust_normalized() {
+        const SAMPLING_RATE: usize = 16;
+
+        // Bins are 1 Hz apart, with one band per valid bin.
+        let buckets = Buckets::from_bands((1..=8).map(|i| (i as Frequency - 0.5, i as Frequency + 0.5)).collect()).unwrap();
+
+        let mut spectrum = vec![0.0; 16];
+        for (i, p) in [100.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0].iter().enumerate() {
+            spectrum[i + 1] = *p;
+        }
+
+        // Max normalization squashes everything but the loud bucket.
+        let by_max = buckets.bucketize_robust_normalized(&spectrum, SAMPLING_RATE, 100.0).unwrap();
+        assert_eq!(1.0, by_max[0]);
+        assert!(by_max[1..].iter().all(|&b| b <= 0.07));
+
+        // The 75th percentile falls between the 6 and 7 buckets, so the rest keep a usable range.
+        let produced = buckets.bucketize_robust_normalized(&spectrum, SAMPLING_RATE, 75.0).unwrap();
+        let reference = 6.25;
+        assert_eq!(1.0, produced[0]);
+        assert_eq!(1.0, produced[7]);
+        for (i, p) in produced.iter().enumerate().take(6).skip(1) {
+            assert_approx_eq!(i as SignalStrength / reference, p);
+        }
+
+        // A silent percentile falls back to the loudest bucket.
+        let mut sparse = vec![0.0; 16];
+        sparse[3] = 2.0;
+        let produced = buckets.bucketize_robust_normalized(&sparse, SAMPLING_RATE, 50.0).unwrap();
+        assert_eq!(vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0], produced);
+
+        assert_eq!(vec![0.0; 8], buckets.bucketize_robust_normalized(&[0.0; 16], SAMPLING_RATE, 95.0).unwrap());
+        assert_eq!(Err(Error::Percentile), buckets.bucketize_robust_normalized(&spectrum, SAMPLING_RATE, 101.0));
+    }
+
+    #[test]
+    fn test_empty_buckets() {
+        const SAMPLING_RATE: usize = 44100;
+
+        let buckets = Buckets::new(20.0, 20000.0, 64).unwrap();
+
+        // With bins about 172 Hz apart, the many narrow low bands miss every bin.
+        let produced = buckets.empty_buckets(256, SAMPLING_RATE).unwrap();
+        assert!(produced.len() > 20);
+        assert_eq!(0, produced[0]);
+        assert!(produced.iter().all(|&i| i < 40));
+
+        // Every reported band really is silent on a flat spectrum, and the rest are not.
+        let bucketized = buckets.bucketize(&[1.0; 256], SAMPLING_RATE).unwrap();
+        for (i, b) in bucketized.iter().enumerate() {
+            assert_eq!(produced.contains(&i), *b == 0.0);
+        }
+
+        // A much larger FFT fills them all.
+        assert!(buckets.empty_buckets(1 << 16, SAMPLING_RATE).unwrap().is_empty());
+
+        // Merged bins count towards the lowest band.
+        let merged = Buckets::new(20.0, 20000.0, 64).unwrap().with_merged_low_bins(1);
+        assert!(!merged.empty_buckets(256, SAMPLING_RATE).unwrap().contains(&0));
+
+        assert_eq!(Err(Error::SamplingRate(0)), buckets.empty_buckets(256, 0));
+    }
+
+    #[test]
+    fn test_deviation_from() {
+        let buckets = Buckets::new(20.0, 20000.0, 4).unwrap();
+
+        let reference = vec![1.0, 0.5, 0.0, 2.0];
+
+        let produced = buckets.deviation_from(&reference, &reference).unwrap();
+        assert_eq!(vec![0.0; 4], produced);
+
+        let current = vec![10.0, 0.05, 1.0, 0.0];
+        let expected = vec![10.0, -10.0, DEVIATION_LIMIT_DB, -DEVIATION_LIMIT_DB];
+
+        let produced = buckets.deviation_from(&current, &reference).unwrap();
+        for (e, p) in expected.into_iter().zip(produced) {
+            assert_approx_eq!(e, p);
+        }
+
+        assert_eq!(Err(Error::BucketsLength(4, 3)), buckets.deviation_from(&current, &[0.0; 3]));
+    }
+
+    #[test]
+    fn test_interchannel_level_difference() {
+        let buckets = Buckets::new(20.0, 20000.0, 3).unwrap();
+
+        let left = vec![2.0, 1.0, 0.25];
+        let right = vec![1.0, 1.0, 0.5];
+
+        let produced = buckets.interchannel_level_difference(&left, &right).unwrap();
+
+        assert!(produced[0] > 0.0);
+        assert_approx_eq!(3.0103, produced[0], 1e-4);
+        assert_eq!(0.0, produced[1]);
+        assert!(produced[2] < 0.0);
+    }
+
+    #[test]
+    fn test_coverage() {
+        const SAMPLING_RATE: usize = 8000;
+
+        // The Nyquist bin itself is only assigned if the upper cutoff is above it.
+        let buckets = Buckets::new(0.001, 4000.1, 8).unwrap();
+        let (range_fraction, bin_fraction) = buckets.coverage(FFT_LEN, SAMPLING_RATE).unwrap();
+        assert_approx_eq!(1.0, range_fraction);
+        assert_eq!(1.0, bin_fraction);
+
+        let buckets = Buckets::new(1000.0, 3000.0, 8).unwrap();
+        let (range_fraction, bin_fraction) = buckets.coverage(FFT_LEN, SAMPLING_RATE).unwrap();
+        assert_approx_eq!(0.5, range_fraction);
+        assert_approx_eq!(0.5, bin_fraction);
+
+        assert_eq!(Err(Error::SamplingRate(0)), buckets.coverage(FFT_LEN, 0));
+    }
+
+    #[test]
+    fn test_apply_equal_loudness() {
+        let buckets = Buckets::new(20.0, 20000.0, 10).unwrap();
+
+        let flat = vec![1.0; 10];
+        let produced = buckets.apply_equal_loudness(&flat, 60.0).unwrap();
+
+        // Both extremes are attenuated relative to the middle of the spectrum.
+        let mid = buckets.locate(1000.0).unwrap();
+        assert!(produced[0] < produced[mid]);
+        assert!(produced[9] < produced[mid]);
+
+        // A-weighting, for comparison.
+        let a_weighting = |f: Frequency| {
+            let f2 = f.powi(2);
+            let r = 12194.0f32.powi(2) * f2.powi(2)
+                / ((f2 + 20.6f32.powi(2)) * ((f2 + 107.7f32.powi(2)) * (f2 + 737.9f32.powi(2))).sqrt() * (f2 + 12194.0f32.powi(2)));
+            (r * 1.2589).powi(2)
+        };
+
+        let differs = buckets.bands().iter().zip(&produced).any(|((lo, hi), p)| {
+            (10.0 * (p / a_weighting((lo * hi).sqrt())).log10()).abs() > 1.0
+        });
+        assert!(differs);
+
+        assert_eq!(Err(Error::PhonLevel), buckets.apply_equal_loudness(&flat, 100.0));
+        assert_eq!(Err(Error::BucketsLength(10, 2)), buckets.apply_equal_loudness(&[1.0; 2], 60.0));
     }
 
     const SAMPLES_PER_PERIOD: usize = 44100;
@@ -211,5 +920,109 @@ mod tests {
         println!("{:?}", produced);
         println!("{:?}", buckets.bands());
     }
-}
 
+    #[test]
+    fn test_bucketize_db() {
+        const SAMPLING_RATE: usize = 64;
+
+        let buckets = Buckets::new_linear(1.0, 31.0, 3).unwrap();
+
+        // One bin per hertz, with the bands covering bins 1-10, 11-20 and 21-30.
+        let mut spectrum = vec![0.0; SAMPLING_RATE];
+        spectrum[5] = 4.0;
+        spectrum[15] = 0.04;
+
+        let produced = buckets.bucketize_db(&spectrum, SAMPLING_RATE, 4.0, -60.0).unwrap();
+        assert_eq!(3, produced.len());
+        assert_approx_eq!(0.0, produced[0]);
+        assert_approx_eq!(-20.0, produced[1]);
+        assert_eq!(-60.0, produced[2]);
+
+        // The linear output is left as is.
+        assert_eq!(vec![4.0, 0.04, 0.0], buckets.bucketize(&spectrum, SAMPLING_RATE).unwrap());
+
+        assert_eq!(Err(Error::ReferenceLevel), buckets.bucketize_db(&spectrum, SAMPLING_RATE, 0.0, -60.0));
+        assert_eq!(Err(Error::DecibelFloor), buckets.bucketize_db(&spectrum, SAMPLING_RATE, 4.0, 0.0));
+    }
+
+    #[test]
+    fn test_bucketize_subset() {
+        use crate::analyzer::Analyzer;
+        use crate::window_kind::WindowKind;
+        use crate::sample::SampleBuffer;
+
+        let buckets = Buckets::new(20.0, 10000.0, 16).unwrap();
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let samples = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN));
+
+        let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+
+        let full = buckets.bucketize(spectrum, SAMPLES_PER_PERIOD).unwrap();
+
+        let indices = vec![0, 3, 10, 15, 7];
+        let produced = buckets.bucketize_subset(spectrum, SAMPLES_PER_PERIOD, &indices).unwrap();
+
+        assert_eq!(indices.len(), produced.len());
+        for (i, p) in indices.into_iter().zip(produced) {
+            assert_eq!(full[i], p);
+        }
+
+        assert_eq!(Err(Error::BucketIndex(16, 16)), buckets.bucketize_subset(spectrum, SAMPLES_PER_PERIOD, &[16]));
+    }
+
+    #[test]
+    fn test_bucketize_with_dc() {
+        use crate::analyzer::Analyzer;
+        use crate::window_kind::WindowKind;
+        use crate::sample::SampleBuffer;
+
+        const DC_OFFSET: f32 = 0.5;
+
+        let buckets = Buckets::new(20.0, 10000.0, 16).unwrap();
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let offset_samples =
+            TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, FREQUENCY, FFT_LEN)
+            .into_iter()
+            .map(|s| s + DC_OFFSET)
+            .collect::<Vec<_>>()
+        ;
+        let samples = SampleBuffer::from(offset_samples);
+
+        let (spectrum, _) = analyzer.analyze(&samples).unwrap();
+
+        let without_dc = buckets.bucketize_with_dc(spectrum, SAMPLES_PER_PERIOD, false).unwrap();
+        let with_dc = buckets.bucketize_with_dc(spectrum, SAMPLES_PER_PERIOD, true).unwrap();
+
+        assert_eq!(buckets.bucketize(spectrum, SAMPLES_PER_PERIOD).unwrap(), without_dc);
+        assert!(with_dc[0] > without_dc[0]);
+        assert_approx_eq!(with_dc[0] - without_dc[0], spectrum[0]);
+        assert_eq!(with_dc[1..], without_dc[1..]);
+    }
+
+    #[test]
+    fn test_with_merged_low_bins() {
+        const SAMPLING_RATE: usize = 64;
+        const NUM_MERGED: usize = 4;
+
+        // With one bin per hertz, bin 1 falls below every band.
+        let buckets = Buckets::new_linear(2.0, 32.0, 6).unwrap();
+        let merged = buckets.clone().with_merged_low_bins(NUM_MERGED);
+
+        let spectrum: Vec<_> = (0..SAMPLING_RATE).map(|i| i as SignalStrength).collect();
+
+        let plain_output = buckets.bucketize(&spectrum, SAMPLING_RATE).unwrap();
+        let merged_output = merged.bucketize(&spectrum, SAMPLING_RATE).unwrap();
+
+        // The lowest band spans 2 to 7 Hz, and takes all of bins 1 to 4 along with its own remaining bins.
+        assert_eq!(2.0 + 3.0 + 4.0 + 5.0 + 6.0, plain_output[0]);
+        assert_eq!(1.0 + 2.0 + 3.0 + 4.0 + 5.0 + 6.0, merged_output[0]);
+        assert_eq!(plain_output[1..], merged_output[1..]);
+
+        let indices: Vec<_> = (0..merged.len()).collect();
+        assert_eq!(merged_output, merged.bucketize_subset(&spectrum, SAMPLING_RATE, &indices).unwrap());
+    }
+}
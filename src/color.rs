@@ -0,0 +1,80 @@
+//! Helpers for rendering bucket values as colors.
+
+use crate::Error;
+use crate::types::SignalStrength;
+
+/// An RGB color, one byte per channel.
+pub type Color = [u8; 3];
+
+/// Finds the color at position `t` along a gradient, interpolating linearly between neighboring stops.
+/// Positions outside of the gradient take the color of the nearest end.
+fn color_at(t: f32, gradient: &[(f32, Color)]) -> Color {
+    let (first_pos, first_color) = gradient[0];
+
+    if t <= first_pos { return first_color }
+
+    for pair in gradient.windows(2) {
+        let (pos_a, color_a) = pair[0];
+        let (pos_b, color_b) = pair[1];
+
+        if t <= pos_b {
+            let frac = if pos_b > pos_a { (t - pos_a) / (pos_b - pos_a) } else { 1.0 };
+
+            let mut color = [0u8; 3];
+            for (c, (a, b)) in color.iter_mut().zip(color_a.iter().zip(&color_b)) {
+                *c = (*a as f32 + (*b as f32 - *a as f32) * frac).round() as u8;
+            }
+
+            return color;
+        }
+    }
+
+    gradient[gradient.len() - 1].1
+}
+
+/// Maps each bucket to a color along a gradient, after normalizing by the loudest bucket.
+/// Gradient stops are pairs of a position from zero to one and a color, in ascending order of position.
+/// If every bucket is silent, all of them map to the color at position zero.
+pub fn map_to_colors(buckets: &[SignalStrength], gradient: &[(f32, Color)]) -> Result<Vec<Color>, Error> {
+    if gradient.is_empty() { Err(Error::Gradient)? }
+
+    let max = buckets.iter().cloned().fold(0.0, SignalStrength::max);
+
+    let colors =
+        buckets
+        .iter()
+        .map(|b| {
+            let t = if max > 0.0 { b / max } else { 0.0 };
+            color_at(t, gradient)
+        })
+        .collect()
+    ;
+
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUE: Color = [0, 0, 255];
+    const GREEN: Color = [0, 255, 0];
+    const RED: Color = [255, 0, 0];
+
+    #[test]
+    fn test_map_to_colors() {
+        let gradient = [(0.0, BLUE), (0.5, GREEN), (1.0, RED)];
+
+        let produced = map_to_colors(&[0.0, 2.0, 1.0, 4.0], &gradient).unwrap();
+        assert_eq!(vec![BLUE, GREEN, [0, 128, 128], RED], produced);
+
+        // Silence has no reference level, and maps to the bottom of the gradient.
+        assert_eq!(vec![BLUE; 3], map_to_colors(&[0.0; 3], &gradient).unwrap());
+
+        // Values outside of the gradient's stops take the nearest end's color.
+        let produced = map_to_colors(&[0.1, 0.5, 1.0], &[(0.25, GREEN), (0.75, RED)]).unwrap();
+        assert_eq!(vec![GREEN, [128, 128, 0], RED], produced);
+
+        assert_eq!(Err(Error::Gradient), map_to_colors(&[1.0], &[]));
+    }
+}
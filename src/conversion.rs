@@ -0,0 +1,196 @@
+//! Conversions between spectrum representations and layouts.
+
+use crate::Error;
+use crate::types::SignalStrength;
+
+/// Converts power values to amplitudes.
+pub fn power_to_amplitude(power: &[SignalStrength]) -> Vec<SignalStrength> {
+    power.iter().map(|p| p.sqrt()).collect()
+}
+
+/// Converts amplitudes to power values.
+pub fn amplitude_to_power(amplitude: &[SignalStrength]) -> Vec<SignalStrength> {
+    amplitude.iter().map(|a| a.powi(2)).collect()
+}
+
+/// Converts power values to decibels, clamping anything below `floor_db` (including zero power) to `floor_db`.
+pub fn power_to_db(power: &[SignalStrength], floor_db: f32) -> Vec<f32> {
+    power.iter().map(|p| (10.0 * p.log10()).max(floor_db)).collect()
+}
+
+/// Converts decibels to power values, treating anything at or below `floor_db` as zero power.
+pub fn db_to_power(db: &[f32], floor_db: f32) -> Vec<SignalStrength> {
+    db.iter().map(|d| if *d > floor_db { 10.0f32.powf(d / 10.0) } else { 0.0 }).collect()
+}
+
+/// Rotates a two-sided spectrum so that the zero frequency is in the center, with negative frequencies before it.
+pub fn fftshift(spectrum: &mut [SignalStrength]) {
+    let n = spectrum.len();
+    spectrum.rotate_right(n / 2);
+}
+
+/// Undoes `fftshift`, moving the zero frequency back to the start.
+pub fn ifftshift(spectrum: &mut [SignalStrength]) {
+    let n = spectrum.len();
+    spectrum.rotate_left(n / 2);
+}
+
+/// Reflects values around a center axis for center-out displays, returning the reversed values followed by the originals.
+pub fn mirror_buckets(buckets: &[SignalStrength]) -> Vec<SignalStrength> {
+    buckets.iter().rev().chain(buckets).cloned().collect()
+}
+
+/// Converts values to compact fixed-point form for transport, mapping `full_scale` to `i16::MAX`.
+/// Values beyond `full_scale` in either direction are clamped.
+pub fn to_i16(values: &[SignalStrength], full_scale: SignalStrength) -> Result<Vec<i16>, Error> {
+    if !(full_scale > 0.0) { Err(Error::FullScale)? }
+
+    Ok(values.iter().map(|v| ((v / full_scale).clamp(-1.0, 1.0) * i16::MAX as SignalStrength).round() as i16).collect())
+}
+
+/// Stretches or squeezes bucketized output to `width` values by linear interpolation, treating each bucket as the
+/// same width on screen. The first and last buckets stay aligned with the edges.
+pub fn resample_buckets(buckets: &[SignalStrength], width: usize) -> Vec<SignalStrength> {
+    let n = buckets.len();
+
+    if n == 0 { return vec![0.0; width] }
+
+    (0..width)
+    .map(|j| {
+        // Sample at the center of each output bucket.
+        let position = ((j as f32 + 0.5) * n as f32 / width as f32 - 0.5).clamp(0.0, (n - 1) as f32);
+
+        let i = position.floor() as usize;
+        let k = (i + 1).min(n - 1);
+        let t = position - i as f32;
+
+        buckets[i] + t * (buckets[k] - buckets[i])
+    })
+    .collect()
+}
+
+/// Blends between two bucket layouts, such as while animating a change in the number of bands.
+/// Both are resampled to `width` values, with a `progress` of zero giving the old layout and one the new.
+pub fn crossfade_buckets(old: &[SignalStrength], new: &[SignalStrength], width: usize, progress: f32) -> Result<Vec<SignalStrength>, Error> {
+    if !(0.0..=1.0).contains(&progress) { Err(Error::BlendFactor)? }
+
+    let blended =
+        resample_buckets(old, width).into_iter().zip(resample_buckets(new, width))
+        .map(|(o, n)| o + progress * (n - o))
+        .collect()
+    ;
+
+    Ok(blended)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLOOR_DB: f32 = -80.0;
+
+    #[test]
+    fn test_amplitude_round_trip() {
+        let power = vec![0.0, 1e-6, 0.25, 1.0, 42.0];
+
+        let amplitude = power_to_amplitude(&power);
+        assert_eq!(vec![0.0, 1e-3, 0.5, 1.0, 42.0f32.sqrt()], amplitude);
+
+        for (e, p) in power.into_iter().zip(amplitude_to_power(&amplitude)) {
+            assert!((e - p).abs() <= e * 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_db_round_trip() {
+        let power = vec![1e-6, 0.001, 0.5, 1.0, 100.0];
+
+        let db = power_to_db(&power, FLOOR_DB);
+        for (e, p) in vec![-60.0, -30.0, -3.0103, 0.0, 20.0].into_iter().zip(&db) {
+            assert_approx_eq!(e, p, 1e-3);
+        }
+
+        for (e, p) in power.into_iter().zip(db_to_power(&db, FLOOR_DB)) {
+            assert!((e - p).abs() <= e * 1e-4);
+        }
+
+        // Values below the floor are clamped, and silence survives the round trip.
+        let db = power_to_db(&[0.0, 1e-10], FLOOR_DB);
+        assert_eq!(vec![FLOOR_DB, FLOOR_DB], db);
+        assert_eq!(vec![0.0, 0.0], db_to_power(&db, FLOOR_DB));
+    }
+
+    #[test]
+    fn test_fftshift() {
+        let inputs_and_expected = vec![
+            (vec![0.0, 1.0, 2.0, 3.0, -4.0, -3.0, -2.0, -1.0], vec![-4.0, -3.0, -2.0, -1.0, 0.0, 1.0, 2.0, 3.0]),
+            (vec![0.0, 1.0, 2.0, -2.0, -1.0], vec![-2.0, -1.0, 0.0, 1.0, 2.0]),
+            (vec![0.0], vec![0.0]),
+            (vec![], vec![]),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let mut produced = input.clone();
+
+            fftshift(&mut produced);
+            assert_eq!(expected, produced);
+
+            ifftshift(&mut produced);
+            assert_eq!(input, produced);
+        }
+    }
+
+    #[test]
+    fn test_mirror_buckets() {
+        let buckets = vec![1.0, 2.0, 3.0];
+
+        let produced = mirror_buckets(&buckets);
+        assert_eq!(vec![3.0, 2.0, 1.0, 1.0, 2.0, 3.0], produced);
+
+        assert_eq!(2 * buckets.len(), produced.len());
+        assert!(produced.iter().eq(produced.iter().rev()));
+
+        assert!(mirror_buckets(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_to_i16() {
+        let produced = to_i16(&[0.0, 0.5, 1.0, 2.0, -1.0, -3.0], 1.0).unwrap();
+        assert_eq!(vec![0, 16384, i16::MAX, i16::MAX, -i16::MAX, -i16::MAX], produced);
+
+        let produced = to_i16(&[0.0, 25.0, 100.0], 100.0).unwrap();
+        assert_eq!(vec![0, 8192, i16::MAX], produced);
+
+        assert_eq!(Err(Error::FullScale), to_i16(&[1.0], 0.0));
+        assert_eq!(Err(Error::FullScale), to_i16(&[1.0], SignalStrength::NAN));
+    }
+
+    #[test]
+    fn test_resample_buckets() {
+        let buckets = vec![0.0, 1.0, 2.0, 3.0];
+
+        assert_eq!(buckets, resample_buckets(&buckets, 4));
+        assert_eq!(vec![0.5, 2.5], resample_buckets(&buckets, 2));
+        assert_eq!(vec![0.0, 0.25, 0.75, 1.25, 1.75, 2.25, 2.75, 3.0], resample_buckets(&buckets, 8));
+
+        assert_eq!(vec![0.0; 3], resample_buckets(&[], 3));
+    }
+
+    #[test]
+    fn test_crossfade_buckets() {
+        let old = vec![1.0, 0.0, 1.0, 0.0];
+        let new = vec![0.0, 1.0];
+
+        // The ends of the transition are each layout on its own, at the display width.
+        assert_eq!(old, crossfade_buckets(&old, &new, 4, 0.0).unwrap());
+        assert_eq!(resample_buckets(&new, 4), crossfade_buckets(&old, &new, 4, 1.0).unwrap());
+        assert_eq!(new, crossfade_buckets(&old, &new, 2, 1.0).unwrap());
+
+        let halfway = crossfade_buckets(&old, &new, 4, 0.5).unwrap();
+        for ((h, o), n) in halfway.iter().zip(resample_buckets(&old, 4)).zip(resample_buckets(&new, 4)) {
+            assert_approx_eq!((o + n) / 2.0, h);
+        }
+
+        assert_eq!(Err(Error::BlendFactor), crossfade_buckets(&old, &new, 4, 1.5));
+    }
+}
@@ -3,11 +3,22 @@
 pub mod types;
 pub mod buckets;
 pub mod analyzer;
+pub mod backend;
 pub mod window_kind;
 pub mod wave;
 pub mod sample;
 pub mod listener;
 pub mod beat;
+pub mod streaming;
+pub mod spectrogram;
+pub mod tracking;
+pub mod note;
+pub mod smoothing;
+pub mod sliding_dft;
+pub mod conversion;
+pub mod weighting;
+pub mod color;
+pub mod mel;
 #[cfg(test)] pub mod test_util;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -23,6 +34,38 @@ pub enum Error {
     DecayFactor,
     TriggerFactor,
     TooFewSamples(usize, usize),
+    HopSize,
+    DecibelFloor,
+    FrameLength(usize, usize),
+    SpectrumLength(usize, usize),
+    SmoothingFactor,
+    TimeConstant,
+    FrameRate,
+    BucketsLength(usize, usize),
+    BucketIndex(usize, usize),
+    BinIndex(usize, usize),
+    DampingFactor,
+    WindowLength,
+    BlendFactor,
+    PhonLevel,
+    Gradient,
+    NonFiniteInput,
+    BandOrder(usize),
+    BandGap(usize),
+    BandOverlap(usize),
+    Fundamental,
+    QuantizationLevels(usize),
+    ReferenceLevel,
+    FullScale,
+    FrameSize,
+    NumCoefficients(usize, usize),
+    SampleRateMismatch(usize, usize),
+    Hysteresis,
+    Percentile,
+    ZoomSpan,
+    FftTooLarge(usize, usize),
+    ThreadSpawn,
+    EmptyBand,
 }
 
 impl std::fmt::Display for Error {
@@ -39,6 +82,38 @@ impl std::fmt::Display for Error {
             Error::DecayFactor => write!(f, "decay factor must be greater than zero"),
             Error::TriggerFactor => write!(f, "trigger factor must be greater than zero"),
             Error::TooFewSamples(e, p) => write!(f, "too few samples in buffer {{ expected: {}, produced: {} }}", e, p),
+            Error::HopSize => write!(f, "hop size must be greater than zero"),
+            Error::DecibelFloor => write!(f, "decibel floor must be less than zero"),
+            Error::FrameLength(e, p) => write!(f, "unexpected frame length {{ expected: {}, produced: {} }}", e, p),
+            Error::SpectrumLength(e, p) => write!(f, "unexpected spectrum length {{ expected: {}, produced: {} }}", e, p),
+            Error::SmoothingFactor => write!(f, "smoothing factor must be at least zero and less than one"),
+            Error::TimeConstant => write!(f, "time constant must be greater than zero"),
+            Error::FrameRate => write!(f, "frame rate must be greater than zero"),
+            Error::BucketsLength(e, p) => write!(f, "unexpected number of buckets {{ expected: {}, produced: {} }}", e, p),
+            Error::BucketIndex(i, n) => write!(f, "bucket index out of range {{ index: {}, len: {} }}", i, n),
+            Error::BinIndex(i, n) => write!(f, "FFT bin index out of range {{ index: {}, len: {} }}", i, n),
+            Error::DampingFactor => write!(f, "damping factor must be greater than zero and at most one"),
+            Error::WindowLength => write!(f, "window length must be greater than zero"),
+            Error::BlendFactor => write!(f, "blend factor must be between zero and one"),
+            Error::PhonLevel => write!(f, "loudness level must be between 0 and 90 phons"),
+            Error::Gradient => write!(f, "gradient must have at least one color stop"),
+            Error::NonFiniteInput => write!(f, "input samples must be finite"),
+            Error::BandOrder(i) => write!(f, "band lower edge must be less than its upper edge {{ index: {} }}", i),
+            Error::BandGap(i) => write!(f, "band does not start where the previous band ends, leaving a gap {{ index: {} }}", i),
+            Error::BandOverlap(i) => write!(f, "band starts before the previous band ends, overlapping it {{ index: {} }}", i),
+            Error::Fundamental => write!(f, "fundamental frequency must be greater than zero and less than Nyquist"),
+            Error::QuantizationLevels(n) => write!(f, "number of quantization levels must be between 1 and 256 {{ found: {} }}", n),
+            Error::ReferenceLevel => write!(f, "reference level must be greater than zero"),
+            Error::FullScale => write!(f, "full scale must be greater than zero"),
+            Error::FrameSize => write!(f, "frame size must be greater than zero"),
+            Error::NumCoefficients(n, m) => write!(f, "number of coefficients must be between one and the number of filters {{ found: {}, max: {} }}", n, m),
+            Error::SampleRateMismatch(e, p) => write!(f, "unexpected sampling rate {{ expected: {}, produced: {} }}", e, p),
+            Error::Hysteresis => write!(f, "hysteresis must be at least zero and less than one"),
+            Error::Percentile => write!(f, "percentile must be between 0 and 100"),
+            Error::ZoomSpan => write!(f, "zoom span must be greater than zero"),
+            Error::FftTooLarge(r, m) => write!(f, "FFT length exceeds the maximum allowed {{ requested: {}, max: {} }}", r, m),
+            Error::ThreadSpawn => write!(f, "failed to spawn analysis thread"),
+            Error::EmptyBand => write!(f, "band must contain at least one valid FFT bin"),
         }
     }
 }
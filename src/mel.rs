@@ -0,0 +1,84 @@
+//! Helpers for working on the mel scale, which spaces frequencies by perceived pitch.
+
+use crate::types::Frequency;
+use crate::types::SignalStrength;
+
+/// Converts a frequency in hertz to mels.
+pub fn hz_to_mel(frequency: Frequency) -> f32 {
+    2595.0 * (1.0 + frequency / 700.0).log10()
+}
+
+/// Converts mels to a frequency in hertz.
+pub fn mel_to_hz(mel: f32) -> Frequency {
+    700.0 * (10.0f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Applies a bank of `n_mels` triangular filters, spaced evenly in mels from zero to Nyquist, to the valid bins of a spectrum.
+/// Each filter peaks at its center and falls to zero at the centers of its neighbors.
+pub fn filterbank_energies(spectrum: &[SignalStrength], sampling_rate: usize, n_mels: usize) -> Vec<SignalStrength> {
+    let n = spectrum.len();
+    let mut energies = vec![0.0; n_mels];
+
+    if n == 0 || n_mels == 0 { return energies }
+
+    let fft_bin_size = sampling_rate as Frequency / n as Frequency;
+    let max_mel = hz_to_mel(sampling_rate as Frequency / 2.0);
+
+    // Edges of every filter, with each filter spanning three consecutive points.
+    let points: Vec<_> = (0..(n_mels + 2)).map(|i| mel_to_hz(max_mel * i as f32 / (n_mels + 1) as f32)).collect();
+
+    for (m, energy) in energies.iter_mut().enumerate() {
+        let (lo, center, hi) = (points[m], points[m + 1], points[m + 2]);
+
+        for (i, power) in spectrum.iter().enumerate().take(n / 2 + 1).skip(1) {
+            let frequency = fft_bin_size * i as Frequency;
+
+            let weight =
+                if frequency > lo && frequency <= center { (frequency - lo) / (center - lo) }
+                else if frequency > center && frequency < hi { (hi - frequency) / (hi - center) }
+                else { 0.0 }
+            ;
+
+            *energy += weight * power;
+        }
+    }
+
+    energies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hz_to_mel() {
+        assert_approx_eq!(0.0, hz_to_mel(0.0));
+        assert_approx_eq!(1000.0, hz_to_mel(1000.0), 0.1);
+
+        for &f in &[0.0, 100.0, 440.0, 1000.0, 8000.0, 22050.0] {
+            assert_approx_eq!(f, mel_to_hz(hz_to_mel(f)), f * 1e-4 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_filterbank_energies() {
+        const SAMPLING_RATE: usize = 16000;
+        const N_MELS: usize = 20;
+
+        // A flat spectrum puts more energy in the wider, higher filters.
+        let produced = filterbank_energies(&vec![1.0; 512], SAMPLING_RATE, N_MELS);
+        assert_eq!(N_MELS, produced.len());
+        for pair in produced.windows(2) {
+            assert!(pair[0] <= pair[1]);
+        }
+
+        // A single bin only reaches the filters on either side of it.
+        let mut spectrum = vec![0.0; 512];
+        spectrum[40] = 1.0;
+        let produced = filterbank_energies(&spectrum, SAMPLING_RATE, N_MELS);
+        assert_approx_eq!(1.0, produced.iter().sum::<f32>());
+        assert!(produced.iter().filter(|e| **e > 0.0).count() <= 2);
+
+        assert_eq!(vec![0.0; N_MELS], filterbank_energies(&[], SAMPLING_RATE, N_MELS));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::types::Frequency;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoteName {
+    C,
+    CSharp,
+    D,
+    DSharp,
+    E,
+    F,
+    FSharp,
+    G,
+    GSharp,
+    A,
+    ASharp,
+    B,
+}
+
+impl NoteName {
+    /// Number of semitones above C in the same octave.
+    pub fn semitone(&self) -> i32 {
+        match self {
+            NoteName::C => 0,
+            NoteName::CSharp => 1,
+            NoteName::D => 2,
+            NoteName::DSharp => 3,
+            NoteName::E => 4,
+            NoteName::F => 5,
+            NoteName::FSharp => 6,
+            NoteName::G => 7,
+            NoteName::GSharp => 8,
+            NoteName::A => 9,
+            NoteName::ASharp => 10,
+            NoteName::B => 11,
+        }
+    }
+
+    /// Calculates the equal-tempered frequency of this note in an octave, tuned so that A4 is `reference_hz`.
+    pub fn frequency(&self, octave: i32, reference_hz: Frequency) -> Frequency {
+        // Distance in semitones from A4.
+        let semitones = (octave - 4) * 12 + self.semitone() - NoteName::A.semitone();
+        reference_hz * 2.0f32.powf(semitones as f32 / 12.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency() {
+        let inputs_and_expected = vec![
+            ((NoteName::A, 4, 440.0), 440.0),
+            ((NoteName::A, 5, 440.0), 880.0),
+            ((NoteName::A, 3, 440.0), 220.0),
+            ((NoteName::C, 4, 440.0), 261.62558),
+            ((NoteName::E, 2, 440.0), 82.40689),
+            ((NoteName::A, 4, 432.0), 432.0),
+        ];
+
+        for ((note, octave, reference_hz), expected) in inputs_and_expected {
+            let produced = note.frequency(octave, reference_hz);
+            assert_approx_eq!(expected, produced, 1e-3);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering as AtomicOrdering;
 use std::collections::VecDeque;
 
 use crate::Error;
@@ -9,13 +11,13 @@ use crate::types::SignalStrength;
 pub type Sample = f32;
 
 #[derive(Clone)]
-pub struct SampleBuffer(Arc<Mutex<VecDeque<(Sample, Sample)>>>);
+pub struct SampleBuffer(Arc<Mutex<VecDeque<(Sample, Sample)>>>, Arc<AtomicUsize>);
 
 impl SampleBuffer {
     /// Create a new sample buffer.
     pub fn new(len: usize) -> Self {
         let buffer = VecDeque::from(vec![(0.0, 0.0); len]);
-        Self(Arc::new(Mutex::from(buffer)))
+        Self(Arc::new(Mutex::from(buffer)), Arc::new(AtomicUsize::new(0)))
     }
 
     /// Get the length of the buffer.
@@ -25,16 +27,33 @@ impl SampleBuffer {
         buffer.len()
     }
 
+    /// Returns true once at least a full buffer's worth of samples has been pushed,
+    /// meaning none of the initial zero-filled samples remain.
+    pub fn is_primed(&self) -> bool {
+        let buffer = self.0.lock().unwrap();
+        self.1.load(AtomicOrdering::SeqCst) >= buffer.len()
+    }
+
+    // Records that a number of sample pairs were pushed, while the buffer is locked.
+    fn record_pushed(&self, buffer_len: usize, num_pushed: usize) {
+        let total = self.1.load(AtomicOrdering::SeqCst).saturating_add(num_pushed).min(buffer_len);
+        self.1.store(total, AtomicOrdering::SeqCst);
+    }
+
     /// Push a slice of stereo samples to the buffer.
     pub fn push(&mut self, sample_pairs: &[(Sample, Sample)]) {
         let mut buffer = self.0.lock().unwrap();
 
         if buffer.len() == 0 { return }
 
-        for sample_pair in sample_pairs.iter() {
-            buffer.pop_front();
-            buffer.push_back(*sample_pair);
-        }
+        // Only the most recent samples that fit in the buffer are kept, so skip the rest.
+        let num_skipped = sample_pairs.len().saturating_sub(buffer.len());
+        let kept = &sample_pairs[num_skipped..];
+
+        buffer.drain(..kept.len());
+        buffer.extend(kept.iter().copied());
+
+        self.record_pushed(buffer.len(), sample_pairs.len());
     }
 
     /// Push a slice of interleaved samples to the buffer.
@@ -43,10 +62,24 @@ impl SampleBuffer {
 
         if buffer.len() == 0 { return }
 
-        for sample_chunk in samples.chunks_exact(2) {
-            buffer.pop_front();
-            buffer.push_back((sample_chunk[0], sample_chunk[1]));
-        }
+        let num_pairs = samples.len() / 2;
+        let num_skipped = num_pairs.saturating_sub(buffer.len());
+        let kept = &samples[2 * num_skipped..2 * num_pairs];
+
+        buffer.drain(..kept.len() / 2);
+        buffer.extend(kept.chunks_exact(2).map(|sample_chunk| (sample_chunk[0], sample_chunk[1])));
+
+        self.record_pushed(buffer.len(), num_pairs);
+    }
+
+    /// Calls `f` with a contiguous view of the buffered sample pairs, oldest first.
+    /// The buffer stays locked for the duration of the call.
+    pub fn with_contiguous<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[(Sample, Sample)]) -> R,
+    {
+        let mut buffer = self.0.lock().unwrap();
+        f(buffer.make_contiguous())
     }
 
     fn iter_starting_at<'a>(&'a self, n: usize) -> SampleBufferIter<'a> {
@@ -80,17 +113,37 @@ impl SampleBuffer {
 
 impl From<Vec<(Sample, Sample)>> for SampleBuffer {
     fn from(v: Vec<(Sample, Sample)>) -> Self {
+        let num_pushed = v.len();
         let buffer: VecDeque<_> = v.into();
-        Self(Arc::new(Mutex::from(buffer)))
+        Self(Arc::new(Mutex::from(buffer)), Arc::new(AtomicUsize::new(num_pushed)))
     }
 }
 
 impl From<Vec<Sample>> for SampleBuffer {
     fn from(v: Vec<Sample>) -> Self {
-        Self(Arc::new(Mutex::from(v.into_iter().map(|s| (s, s)).collect::<VecDeque<_>>())))
+        let num_pushed = v.len();
+        Self(Arc::new(Mutex::from(v.into_iter().map(|s| (s, s)).collect::<VecDeque<_>>())), Arc::new(AtomicUsize::new(num_pushed)))
     }
 }
 
+/// Folds stereo down to mono as `mid + side_gain * side`, where `mid = (l + r) / 2` and `side = (l - r) / 2`.
+/// A `side_gain` of zero keeps only the center image, and one reproduces the left channel.
+pub fn stereo_to_weighted_mono(left: &[Sample], right: &[Sample], side_gain: f32) -> Result<Vec<Sample>, Error> {
+    if left.len() != right.len() { Err(Error::NumSamples(left.len(), right.len()))? }
+
+    let mono =
+        left.iter().zip(right)
+        .map(|(l, r)| {
+            let mid = (l + r) / 2.0;
+            let side = (l - r) / 2.0;
+            mid + side_gain * side
+        })
+        .collect()
+    ;
+
+    Ok(mono)
+}
+
 pub struct SampleBufferIter<'a> {
     buffer: MutexGuard<'a, VecDeque<(Sample, Sample)>>,
     index: usize,
@@ -115,6 +168,24 @@ impl<'a> SampleBufferIter<'a> {
             }
         }
     }
+
+    /// Calculates the ratio of the peak absolute sample to the RMS of the samples in this iterator.
+    /// Silence has a crest factor of zero.
+    pub fn crest_factor(self) -> SignalStrength {
+        let (peak, sum_squares, n) =
+            self
+            .fold((0.0f32, 0.0, 0), |(p, ss, c), (l, r)| {
+                (p.max(l.abs()).max(r.abs()), ss + l.powi(2) + r.powi(2), c + 1)
+            })
+        ;
+
+        if n == 0 { return 0.0 }
+
+        let rms = (sum_squares / (2 * n) as SignalStrength).sqrt();
+
+        if rms > 0.0 { peak / rms }
+        else { 0.0 }
+    }
 }
 
 impl Iterator for SampleBufferIter<'_> {
@@ -158,4 +229,90 @@ mod tests {
             assert_approx_eq!(expected, produced);
         }
     }
+
+    #[test]
+    fn test_stereo_to_weighted_mono() {
+        let left = vec![1.0, 0.5, -0.25, 0.0];
+        let right = vec![0.0, 0.5, 0.25, -1.0];
+
+        // Without any side content, the result is the average of both channels.
+        let produced = stereo_to_weighted_mono(&left, &right, 0.0).unwrap();
+        assert_eq!(vec![0.5, 0.5, 0.0, -0.5], produced);
+
+        let produced = stereo_to_weighted_mono(&left, &right, 0.5).unwrap();
+        assert_eq!(vec![0.75, 0.5, -0.125, -0.25], produced);
+
+        assert_eq!(left, stereo_to_weighted_mono(&left, &right, 1.0).unwrap());
+
+        assert_eq!(Err(Error::NumSamples(4, 1)), stereo_to_weighted_mono(&left, &[0.0], 0.0));
+    }
+
+    #[test]
+    fn test_push() {
+        const LEN: usize = 8;
+
+        let pairs: Vec<_> = (0..20).map(|i| (i as Sample, -i as Sample)).collect();
+        let interleaved: Vec<_> = pairs.iter().flat_map(|&(l, r)| vec![l, r]).collect();
+
+        // Smaller than, equal to, and larger than the buffer.
+        for &n in &[3, LEN, 20] {
+            let mut buffer = SampleBuffer::new(LEN);
+            let mut interleaved_buffer = SampleBuffer::new(LEN);
+
+            buffer.push(&pairs[..n]);
+            interleaved_buffer.push_interleaved(&interleaved[..2 * n]);
+
+            let mut expected = vec![(0.0, 0.0); LEN.saturating_sub(n)];
+            expected.extend_from_slice(&pairs[n.saturating_sub(LEN)..n]);
+
+            assert_eq!(expected, buffer.iter().collect::<Vec<_>>());
+            assert_eq!(expected, interleaved_buffer.iter().collect::<Vec<_>>());
+        }
+
+        // Pushes build on each other, and a trailing unpaired sample is ignored.
+        let mut buffer = SampleBuffer::new(LEN);
+        buffer.push(&pairs[..5]);
+        buffer.push_interleaved(&interleaved[10..17]);
+        assert_eq!(pairs[0..8].to_vec(), buffer.iter().collect::<Vec<_>>());
+
+        // An empty buffer stays empty.
+        let mut buffer = SampleBuffer::new(0);
+        buffer.push(&pairs);
+        assert_eq!(0, buffer.len());
+    }
+
+    #[test]
+    fn test_with_contiguous() {
+        let mut buffer = SampleBuffer::new(4);
+
+        // Pushing rotates the underlying ring, which the view hides.
+        buffer.push(&[(1.0, -1.0), (2.0, -2.0), (3.0, -3.0)]);
+
+        let produced = buffer.with_contiguous(|pairs| pairs.to_vec());
+        assert_eq!(vec![(0.0, 0.0), (1.0, -1.0), (2.0, -2.0), (3.0, -3.0)], produced);
+        assert_eq!(produced, buffer.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_is_primed() {
+        let mut buffer = SampleBuffer::new(8);
+        let shared = buffer.clone();
+
+        assert!(!buffer.is_primed());
+
+        buffer.push(&[(1.0, 1.0); 5]);
+        assert!(!shared.is_primed());
+
+        buffer.push_interleaved(&[1.0; 4]);
+        assert!(!shared.is_primed());
+
+        buffer.push(&[(1.0, 1.0)]);
+        assert!(shared.is_primed());
+
+        buffer.push(&[(1.0, 1.0); 100]);
+        assert!(shared.is_primed());
+
+        // Buffers built from existing samples have no startup transient.
+        assert!(SampleBuffer::from(vec![0.0; 8]).is_primed());
+    }
 }
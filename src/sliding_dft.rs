@@ -0,0 +1,126 @@
+//! Updates a DFT incrementally with each new sample.
+
+use std::f32::consts::PI;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+use crate::Error;
+use crate::sample::Sample;
+
+/// A sliding DFT over the most recent `len` samples.
+///
+/// Each push updates every bin in constant time, instead of recomputing the full transform.
+/// Since each update builds on the last, rounding errors accumulate and the recursion is only marginally stable.
+/// A `damping` factor slightly below one (e.g. 0.9999) makes old errors decay away,
+/// at the cost of the output no longer exactly matching a full DFT of the window.
+pub struct SlidingDft {
+    damping: f32,
+
+    // Damping factor raised to the length of the window, applied to the sample leaving the window.
+    damping_len: f32,
+
+    twiddles: Vec<Complex<Sample>>,
+
+    // Circular buffer of the samples in the window, and the index of the oldest one.
+    history: Vec<Sample>,
+    oldest: usize,
+
+    bins: Vec<Complex<Sample>>,
+}
+
+impl SlidingDft {
+    pub fn new(len: usize, damping: f32) -> Result<Self, Error> {
+        if !(len > 0) { Err(Error::WindowLength)? }
+        if !(damping > 0.0 && damping <= 1.0) { Err(Error::DampingFactor)? }
+
+        let damping_len = damping.powi(len as i32);
+
+        let twiddles =
+            (0..len)
+            .map(|k| Complex::from_polar(&damping, &(2.0 * PI * k as f32 / len as f32)))
+            .collect()
+        ;
+
+        Ok(Self {
+            damping,
+            damping_len,
+            twiddles,
+            history: vec![0.0; len],
+            oldest: 0,
+            bins: vec![Complex::zero(); len],
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    #[inline]
+    pub fn damping(&self) -> f32 {
+        self.damping
+    }
+
+    /// Pushes a new sample into the window, returning the updated bins.
+    pub fn push(&mut self, sample: Sample) -> &[Complex<Sample>] {
+        let delta = sample - self.damping_len * self.history[self.oldest];
+
+        self.history[self.oldest] = sample;
+        self.oldest = (self.oldest + 1) % self.history.len();
+
+        for (bin, twiddle) in self.bins.iter_mut().zip(&self.twiddles) {
+            *bin = twiddle * (*bin + delta);
+        }
+
+        &self.bins
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::analyzer::Analyzer;
+    use crate::sample::SampleBuffer;
+    use crate::test_util::TestUtil;
+    use crate::window_kind::WindowKind;
+
+    const LEN: usize = 64;
+    const NUM_SAMPLES: usize = 300;
+
+    fn compare_with_fft(damping: f32, tolerance: f32) {
+        let samples = TestUtil::generate_wave_samples(44100, 3000.0, NUM_SAMPLES);
+
+        let mut sliding_dft = SlidingDft::new(LEN, damping).unwrap();
+        let mut bins = Vec::new();
+        for sample in samples.iter() {
+            bins = sliding_dft.push(*sample).to_vec();
+        }
+
+        let mut analyzer = Analyzer::new(LEN, WindowKind::Rectangular);
+        let window = SampleBuffer::from(samples[NUM_SAMPLES - LEN..].to_vec());
+        let (spectrum, _) = analyzer.analyze(&window).unwrap();
+
+        let peak = spectrum.iter().cloned().fold(0.0, f32::max);
+
+        for (bin, expected) in bins.iter().zip(spectrum) {
+            assert!((bin.norm_sqr() - expected).abs() < tolerance * peak);
+        }
+    }
+
+    #[test]
+    fn test_push() {
+        compare_with_fft(1.0, 1e-4);
+        compare_with_fft(0.9999, 1e-2);
+
+        assert!(SlidingDft::new(LEN, 0.0).is_err());
+        assert!(SlidingDft::new(LEN, 1.1).is_err());
+        assert!(SlidingDft::new(0, 1.0).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+//! Stateful helpers that smooth bucket output across frames.
+
+use crate::Error;
+use crate::analyzer::Analyzer;
+use crate::buckets::Buckets;
+use crate::sample::Sample;
+use crate::types::SignalStrength;
+
+/// The smoothed levels of a `DecibelSmoother` at a point in time, independent of its settings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecibelSmootherSnapshot {
+    levels_db: Vec<f32>,
+}
+
+/// Smooths values in the decibel domain, so that rising and falling levels move at the same perceived rate.
+pub struct DecibelSmoother {
+    // Fraction of the previous level retained on each frame.
+    factor: f32,
+    floor_db: f32,
+
+    levels_db: Vec<f32>,
+    output: Vec<SignalStrength>,
+}
+
+impl DecibelSmoother {
+    /// Creates a new smoother.
+    /// After `time_constant` seconds, the output has covered about 63% of a step in decibels.
+    /// Values at or below `floor_db` are treated as silence.
+    pub fn new(time_constant: f32, frame_rate: f32, floor_db: f32) -> Result<Self, Error> {
+        if !(time_constant > 0.0) { Err(Error::TimeConstant)? }
+        if !(frame_rate > 0.0) { Err(Error::FrameRate)? }
+        if !(floor_db < 0.0) { Err(Error::DecibelFloor)? }
+
+        let factor = (-1.0 / (time_constant * frame_rate)).exp();
+
+        Ok(Self {
+            factor,
+            floor_db,
+            levels_db: Vec::new(),
+            output: Vec::new(),
+        })
+    }
+
+    /// Smooths a new frame of values, returning the smoothed frame.
+    /// If the number of values changes, the smoother restarts from the new frame.
+    pub fn smooth(&mut self, buckets: &[SignalStrength]) -> &[SignalStrength] {
+        let floor_db = self.floor_db;
+        let to_db = |p: SignalStrength| (10.0 * p.log10()).max(floor_db);
+
+        if self.levels_db.len() != buckets.len() {
+            self.levels_db = buckets.iter().cloned().map(to_db).collect();
+            self.output = vec![0.0; buckets.len()];
+        }
+        else {
+            for (level, b) in self.levels_db.iter_mut().zip(buckets) {
+                *level = self.factor * *level + (1.0 - self.factor) * to_db(*b);
+            }
+        }
+
+        for (o, level) in self.output.iter_mut().zip(&self.levels_db) {
+            *o = if *level > floor_db { 10.0f32.powf(level / 10.0) } else { 0.0 };
+        }
+
+        &self.output
+    }
+
+    /// Captures the current smoothed levels.
+    pub fn snapshot(&self) -> DecibelSmootherSnapshot {
+        DecibelSmootherSnapshot { levels_db: self.levels_db.clone() }
+    }
+
+    /// Restores previously captured levels, keeping this smoother's own settings.
+    /// The snapshot may come from a smoother with different settings.
+    pub fn restore(&mut self, snapshot: DecibelSmootherSnapshot) {
+        self.output = vec![0.0; snapshot.levels_db.len()];
+        self.levels_db = snapshot.levels_db;
+    }
+}
+
+/// Smooths bucket output exponentially, blending each new frame with the previous output.
+pub struct Smoother {
+    // Fraction of the previous output retained on each frame.
+    factor: f32,
+    fast_attack: bool,
+
+    output: Vec<SignalStrength>,
+}
+
+impl Smoother {
+    /// Creates a new smoother, where `out = factor * prev + (1 - factor) * new`.
+    /// A factor of zero follows the input exactly, values closer to one respond more slowly.
+    pub fn new(factor: f32) -> Result<Self, Error> {
+        if !(0.0..1.0).contains(&factor) { Err(Error::SmoothingFactor)? }
+
+        Ok(Self {
+            factor,
+            fast_attack: false,
+            output: Vec::new(),
+        })
+    }
+
+    /// Makes rising values jump straight to the input, so that only falling values are smoothed.
+    pub fn with_fast_attack(mut self, fast_attack: bool) -> Self {
+        self.fast_attack = fast_attack;
+        self
+    }
+
+    /// Smooths a new frame of buckets, returning the smoothed frame.
+    /// The first frame, and any frame with a different number of buckets, is passed through as is.
+    pub fn smooth(&mut self, buckets: &[SignalStrength]) -> &[SignalStrength] {
+        if self.output.len() != buckets.len() {
+            self.output.clear();
+            self.output.extend_from_slice(buckets);
+        }
+        else {
+            for (o, b) in self.output.iter_mut().zip(buckets) {
+                *o =
+                    if self.fast_attack && *b > *o { *b }
+                    else { self.factor * *o + (1.0 - self.factor) * b }
+                ;
+            }
+        }
+
+        &self.output
+    }
+}
+
+/// Analyzes and bucketizes frames of mono samples, smoothing the output with a `Smoother`.
+pub struct SmoothedAnalyzer {
+    analyzer: Analyzer,
+    buckets: Buckets,
+    sampling_rate: usize,
+    smoother: Smoother,
+
+    // Power spectrum of the last frame, whose storage is handed back to the analyzer on each push.
+    spectrum: Vec<SignalStrength>,
+}
+
+impl SmoothedAnalyzer {
+    pub fn new(analyzer: Analyzer, buckets: Buckets, sampling_rate: usize, smoother: Smoother) -> Result<Self, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+
+        Ok(Self {
+            analyzer,
+            buckets,
+            sampling_rate,
+            smoother,
+            spectrum: Vec::new(),
+        })
+    }
+
+    /// Pushes a frame of mono samples, returning both the raw and the smoothed buckets, such as to compare them while tuning.
+    pub fn push(&mut self, samples: &[Sample]) -> Result<(Vec<SignalStrength>, Vec<SignalStrength>), Error> {
+        let recycled = std::mem::take(&mut self.spectrum);
+        self.spectrum = self.analyzer.analyze_in_place(samples, recycled)?;
+
+        let raw = self.buckets.bucketize(&self.spectrum, self.sampling_rate)?;
+        let smoothed = self.smoother.smooth(&raw).to_vec();
+
+        Ok((raw, smoothed))
+    }
+}
+
+/// Holds a decaying maximum of each bucket, leaving a fading trail behind each frame.
+pub struct Persistence {
+    // Fraction of the held level retained on each frame.
+    decay: f32,
+
+    levels: Vec<SignalStrength>,
+}
+
+impl Persistence {
+    /// Creates a new persistence display, where the held level of each bucket is multiplied by `decay` on each frame.
+    pub fn new(decay: f32) -> Result<Self, Error> {
+        if !(0.0..1.0).contains(&decay) { Err(Error::SmoothingFactor)? }
+
+        Ok(Self {
+            decay,
+            levels: Vec::new(),
+        })
+    }
+
+    #[inline]
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    /// Pushes a new frame of buckets, returning the held levels.
+    /// Each level jumps up to follow louder input, and otherwise decays exponentially.
+    /// The first frame, and any frame with a different number of buckets, is passed through as is.
+    pub fn push(&mut self, buckets: &[SignalStrength]) -> &[SignalStrength] {
+        if self.levels.len() != buckets.len() {
+            self.levels.clear();
+            self.levels.extend_from_slice(buckets);
+        }
+        else {
+            for (level, b) in self.levels.iter_mut().zip(buckets) {
+                *level = (*level * self.decay).max(*b);
+            }
+        }
+
+        &self.levels
+    }
+}
+
+/// Holds the maximum of each bin over a whole session, for a "maximum ever seen" overlay.
+/// Unlike `Persistence`, the held levels never fall.
+#[derive(Default)]
+pub struct MaxHoldSpectrum {
+    max: Vec<SignalStrength>,
+}
+
+impl MaxHoldSpectrum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes a new spectrum, raising each held level to the new value if it is louder.
+    /// The first spectrum after creating or resetting starts a new session, and sets the length the rest must have.
+    pub fn push(&mut self, spectrum: &[SignalStrength]) -> Result<(), Error> {
+        if self.max.is_empty() {
+            self.max.extend_from_slice(spectrum);
+            return Ok(())
+        }
+
+        if self.max.len() != spectrum.len() { Err(Error::SpectrumLength(self.max.len(), spectrum.len()))? }
+
+        for (m, p) in self.max.iter_mut().zip(spectrum) {
+            *m = m.max(*p);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the held levels, which are empty before the first push.
+    #[inline]
+    pub fn max(&self) -> &[SignalStrength] {
+        &self.max
+    }
+
+    /// Clears the held levels, starting a new session.
+    pub fn reset(&mut self) {
+        self.max.clear();
+    }
+}
+
+/// Smooths bucket output with separate factors for rising and falling values, which may differ per bucket.
+/// Each factor is the fraction of the previous level retained on each frame,
+/// so zero follows the input exactly and values closer to one respond more slowly.
+pub struct AsymmetricSmoother {
+    attack: Vec<f32>,
+    release: Vec<f32>,
+
+    // Smoothed levels, empty until the first frame is seen.
+    levels: Vec<SignalStrength>,
+}
+
+impl AsymmetricSmoother {
+    /// Creates a new smoother from per-bucket attack and release factors.
+    pub fn new(attack: Vec<f32>, release: Vec<f32>) -> Result<Self, Error> {
+        if attack.len() != release.len() { Err(Error::BucketsLength(attack.len(), release.len()))? }
+
+        for factor in attack.iter().chain(&release) {
+            if !(0.0..1.0).contains(factor) { Err(Error::SmoothingFactor)? }
+        }
+
+        Ok(Self {
+            attack,
+            release,
+            levels: Vec::new(),
+        })
+    }
+
+    /// Creates a new smoother, calling `f` with each bucket index to get that bucket's attack and release factors.
+    pub fn from_fn<F>(num_buckets: usize, f: F) -> Result<Self, Error>
+    where
+        F: Fn(usize) -> (f32, f32),
+    {
+        let (attack, release) = (0..num_buckets).map(f).unzip();
+        Self::new(attack, release)
+    }
+
+    /// Smooths a new frame of buckets, returning the smoothed frame.
+    /// The first frame is passed through as is.
+    pub fn smooth(&mut self, buckets: &[SignalStrength]) -> Result<&[SignalStrength], Error> {
+        if self.attack.len() != buckets.len() { Err(Error::BucketsLength(self.attack.len(), buckets.len()))? }
+
+        if self.levels.is_empty() {
+            self.levels.extend_from_slice(buckets);
+        }
+        else {
+            for ((level, b), (attack, release)) in self.levels.iter_mut().zip(buckets).zip(self.attack.iter().zip(&self.release)) {
+                let factor = if *b > *level { attack } else { release };
+                *level = factor * *level + (1.0 - factor) * b;
+            }
+        }
+
+        Ok(&self.levels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_RATE: f32 = 60.0;
+    const TIME_CONSTANT: f32 = 0.25;
+    const FLOOR_DB: f32 = -90.0;
+    const NUM_FRAMES: usize = 30;
+
+    fn to_db(p: SignalStrength) -> f32 {
+        10.0 * p.log10()
+    }
+
+    // Smooths a step from `from` to `to`, returning the output levels in decibels.
+    fn smooth_step_db(from: SignalStrength, to: SignalStrength) -> Vec<f32> {
+        let mut smoother = DecibelSmoother::new(TIME_CONSTANT, FRAME_RATE, FLOOR_DB).unwrap();
+        smoother.smooth(&[from]);
+        (0..NUM_FRAMES).map(|_| to_db(smoother.smooth(&[to])[0])).collect()
+    }
+
+    // Same as above, but smoothing the power directly with the same factor.
+    fn smooth_step_linear_db(from: SignalStrength, to: SignalStrength) -> Vec<f32> {
+        let factor = (-1.0 / (TIME_CONSTANT * FRAME_RATE)).exp();
+        let mut level = from;
+        (0..NUM_FRAMES).map(|_| { level = factor * level + (1.0 - factor) * to; to_db(level) }).collect()
+    }
+
+    #[test]
+    fn test_smooth() {
+        // Rising and falling between -40 dB and 0 dB mirror each other on a decibel axis.
+        let rise = smooth_step_db(0.0001, 1.0);
+        let fall = smooth_step_db(1.0, 0.0001);
+
+        for (r, f) in rise.iter().zip(&fall) {
+            assert_approx_eq!(r + 40.0, -f, 1e-3);
+        }
+
+        // Linear smoothing rises much faster than it falls.
+        let linear_rise = smooth_step_linear_db(0.0001, 1.0);
+        let linear_fall = smooth_step_linear_db(1.0, 0.0001);
+
+        assert!(linear_rise[0] + 40.0 > 4.0 * -linear_fall[0]);
+
+        // After one time constant, about 63% of the step has been covered.
+        let frames_per_time_constant = (TIME_CONSTANT * FRAME_RATE) as usize;
+        let covered = (rise[frames_per_time_constant - 1] + 40.0) / 40.0;
+        assert_approx_eq!(1.0 - (-1.0f32).exp(), covered, 1e-3);
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let mut smoother = DecibelSmoother::new(TIME_CONSTANT, FRAME_RATE, FLOOR_DB).unwrap();
+
+        smoother.smooth(&[1.0, 0.01]);
+        smoother.smooth(&[0.1, 0.1]);
+
+        let snapshot = smoother.snapshot();
+        let expected = smoother.smooth(&[0.5, 0.5]).to_vec();
+
+        smoother.smooth(&[0.0, 1.0]);
+        smoother.restore(snapshot.clone());
+        assert_eq!(expected, smoother.smooth(&[0.5, 0.5]));
+
+        // A smoother with different settings starts from the same levels.
+        let mut other = DecibelSmoother::new(TIME_CONSTANT * 2.0, FRAME_RATE, FLOOR_DB).unwrap();
+        other.restore(snapshot);
+        let produced = other.smooth(&[0.5, 0.5]);
+        assert_eq!(2, produced.len());
+        assert!(produced[0] > expected[0]);
+    }
+
+    #[test]
+    fn test_smooth_floor_and_reset() {
+        let mut smoother = DecibelSmoother::new(TIME_CONSTANT, FRAME_RATE, FLOOR_DB).unwrap();
+
+        assert_eq!(&[0.0, 1.0], smoother.smooth(&[0.0, 1.0]));
+
+        // A change in length restarts from the new frame.
+        let produced = smoother.smooth(&[0.5]);
+        assert_eq!(1, produced.len());
+        assert_approx_eq!(0.5, produced[0]);
+
+        assert_eq!(Err(Error::TimeConstant), DecibelSmoother::new(0.0, FRAME_RATE, FLOOR_DB).map(|_| ()));
+        assert_eq!(Err(Error::FrameRate), DecibelSmoother::new(TIME_CONSTANT, 0.0, FLOOR_DB).map(|_| ()));
+        assert_eq!(Err(Error::DecibelFloor), DecibelSmoother::new(TIME_CONSTANT, FRAME_RATE, 0.0).map(|_| ()));
+    }
+
+    #[test]
+    fn test_smoother() {
+        let mut smoother = Smoother::new(0.75).unwrap();
+
+        assert_eq!(&[0.0, 1.0], smoother.smooth(&[0.0, 1.0]));
+        assert_eq!(&[0.25, 0.75], smoother.smooth(&[1.0, 0.0]));
+        assert_eq!(&[0.4375, 0.5625], smoother.smooth(&[1.0, 0.0]));
+
+        // A change in length restarts from the new frame.
+        assert_eq!(&[0.5], smoother.smooth(&[0.5]));
+
+        assert_eq!(Err(Error::SmoothingFactor), Smoother::new(1.0).map(|_| ()));
+        assert_eq!(Err(Error::SmoothingFactor), Smoother::new(-0.1).map(|_| ()));
+    }
+
+    #[test]
+    fn test_smoother_fast_attack() {
+        let mut smoother = Smoother::new(0.5).unwrap().with_fast_attack(true);
+
+        smoother.smooth(&[0.0]);
+
+        // Rises instantly, then decays by the factor.
+        assert_eq!(&[1.0], smoother.smooth(&[1.0]));
+        assert_eq!(&[0.5], smoother.smooth(&[0.0]));
+        assert_eq!(&[0.25], smoother.smooth(&[0.0]));
+        assert_eq!(&[0.75], smoother.smooth(&[0.75]));
+    }
+
+    #[test]
+    fn test_smoothed_analyzer() {
+        use crate::sample::SampleBuffer;
+        use crate::test_util::TestUtil;
+        use crate::window_kind::WindowKind;
+
+        const FFT_LEN: usize = 256;
+        const SAMPLING_RATE: usize = 44100;
+
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        let mut plain = analyzer.clone();
+        let mut smoothed_analyzer = SmoothedAnalyzer::new(analyzer, buckets.clone(), SAMPLING_RATE, Smoother::new(0.5).unwrap()).unwrap();
+
+        let silence = vec![0.0; FFT_LEN];
+        let tone = TestUtil::generate_wave_samples(SAMPLING_RATE, 1000.0, FFT_LEN);
+
+        smoothed_analyzer.push(&silence).unwrap();
+
+        // The raw output matches a plain analyzer, while the smoothed output is still catching up to the tone.
+        for _ in 0..4 {
+            let (raw, smoothed) = smoothed_analyzer.push(&tone).unwrap();
+
+            let expected = plain.process(&SampleBuffer::from(tone.clone()), &buckets, SAMPLING_RATE).unwrap();
+            assert_eq!(expected, raw);
+
+            let loudest = (0..raw.len()).max_by(|&a, &b| raw[a].partial_cmp(&raw[b]).unwrap()).unwrap();
+            assert!(smoothed[loudest] < raw[loudest]);
+        }
+
+        assert_eq!(Err(Error::NumSamples(FFT_LEN, 4)), smoothed_analyzer.push(&[0.0; 4]));
+    }
+
+    #[test]
+    fn test_persistence() {
+        const DECAY: f32 = 0.8;
+
+        let mut persistence = Persistence::new(DECAY).unwrap();
+        assert_eq!(DECAY, persistence.decay());
+
+        persistence.push(&[0.0, 0.5]);
+        assert_eq!(&[1.0, 0.5], persistence.push(&[1.0, 0.5]));
+
+        // A spike decays exponentially over silent frames, while a steady input is held.
+        for n in 1..=20 {
+            let produced = persistence.push(&[0.0, 0.5]);
+            assert_approx_eq!(DECAY.powi(n), produced[0]);
+            assert_eq!(0.5, produced[1]);
+        }
+
+        assert_eq!(Err(Error::SmoothingFactor), Persistence::new(1.0).map(|_| ()));
+    }
+
+    #[test]
+    fn test_max_hold_spectrum() {
+        let mut max_hold = MaxHoldSpectrum::new();
+        assert!(max_hold.max().is_empty());
+
+        let frames = [
+            [0.5, 0.0, 0.25, 1.0],
+            [0.25, 2.0, 0.0, 0.5],
+            [0.0, 0.0, 0.75, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ];
+
+        for frame in &frames {
+            max_hold.push(frame).unwrap();
+        }
+
+        assert_eq!(&[0.5, 2.0, 0.75, 1.0], max_hold.max());
+
+        // A spectrum of the wrong length leaves the held levels alone.
+        assert_eq!(Err(Error::SpectrumLength(4, 2)), max_hold.push(&[3.0, 3.0]));
+        assert_eq!(&[0.5, 2.0, 0.75, 1.0], max_hold.max());
+
+        max_hold.reset();
+        assert!(max_hold.max().is_empty());
+
+        max_hold.push(&[0.1, 0.2]).unwrap();
+        assert_eq!(&[0.1, 0.2], max_hold.max());
+    }
+
+    #[test]
+    fn test_asymmetric_smoother() {
+        // Slow bass, snappy treble.
+        let mut smoother = AsymmetricSmoother::from_fn(2, |i| if i == 0 { (0.9, 0.95) } else { (0.1, 0.5) }).unwrap();
+
+        assert_eq!(&[0.0, 0.0], smoother.smooth(&[0.0, 0.0]).unwrap());
+
+        let produced = smoother.smooth(&[1.0, 1.0]).unwrap();
+        assert_approx_eq!(0.1, produced[0]);
+        assert_approx_eq!(0.9, produced[1]);
+
+        let produced = smoother.smooth(&[0.0, 0.0]).unwrap().to_vec();
+        assert_approx_eq!(0.095, produced[0]);
+        assert_approx_eq!(0.45, produced[1]);
+
+        assert_eq!(Err(Error::BucketsLength(2, 3)), smoother.smooth(&[0.0; 3]).map(|_| ()));
+        assert_eq!(Err(Error::BucketsLength(2, 1)), AsymmetricSmoother::new(vec![0.5; 2], vec![0.5]).map(|_| ()));
+        assert_eq!(Err(Error::SmoothingFactor), AsymmetricSmoother::new(vec![0.5, 1.0], vec![0.5; 2]).map(|_| ()));
+    }
+}
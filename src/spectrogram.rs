@@ -0,0 +1,227 @@
+//! Helpers for working with sequences of spectra over time.
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+use crate::Error;
+use crate::analyzer::Analyzer;
+use crate::sample::Sample;
+use crate::sample::SampleBuffer;
+use crate::types::SignalStrength;
+use crate::conversion;
+
+/// Calculates the number of frames produced by `spectrogram` for an input of a given length.
+pub fn frame_count(input_len: usize, fft_len: usize, hop: usize) -> Result<usize, Error> {
+    if !(hop > 0) { Err(Error::HopSize)? }
+
+    if input_len < fft_len { Ok(0) }
+    else { Ok((input_len - fft_len) / hop + 1) }
+}
+
+/// Calculates the spectrum of each full frame of mono samples, with frames starting `hop` samples apart.
+/// Trailing samples that do not fill a frame are ignored.
+pub fn spectrogram(analyzer: &mut Analyzer, samples: &[Sample], hop: usize) -> Result<Vec<Vec<SignalStrength>>, Error> {
+    let fft_len = analyzer.len();
+    let num_frames = frame_count(samples.len(), fft_len, hop)?;
+
+    let mut frames = Vec::with_capacity(num_frames);
+
+    for i in 0..num_frames {
+        let start = i * hop;
+        let frame = SampleBuffer::from(samples[start..start + fft_len].to_vec());
+
+        let (spectrum, _) = analyzer.analyze(&frame)?;
+        frames.push(spectrum.to_vec());
+    }
+
+    Ok(frames)
+}
+
+/// Estimates the magnitude-squared coherence of two channels per FFT bin, averaging over frames `hop` samples apart.
+/// Values range from zero for unrelated channels to one for linearly related ones.
+pub fn coherence(x: &[Sample], y: &[Sample], analyzer: &mut Analyzer, hop: usize) -> Result<Vec<f32>, Error> {
+    if x.len() != y.len() { Err(Error::NumSamples(x.len(), y.len()))? }
+
+    let fft_len = analyzer.len();
+    let num_frames = frame_count(x.len(), fft_len, hop)?;
+
+    if !(num_frames > 0) { Err(Error::TooFewSamples(fft_len, x.len()))? }
+
+    let mut sum_xy = vec![Complex::<Sample>::zero(); fft_len];
+    let mut sum_xx = vec![0.0; fft_len];
+    let mut sum_yy = vec![0.0; fft_len];
+
+    for i in 0..num_frames {
+        let frame = i * hop..i * hop + fft_len;
+
+        analyzer.accumulate_cross_spectra(&x[frame.clone()], &y[frame], &mut sum_xy, &mut sum_xx, &mut sum_yy)?;
+    }
+
+    let coherence =
+        sum_xy.iter().zip(sum_xx.iter().zip(&sum_yy))
+        .map(|(xy, (xx, yy))| {
+            let denominator = xx * yy;
+            if denominator > 0.0 { (xy.norm_sqr() / denominator).min(1.0) } else { 0.0 }
+        })
+        .collect()
+    ;
+
+    Ok(coherence)
+}
+
+/// Converts every frame of a spectrogram from power to decibels, using the same floor throughout.
+pub fn spectrogram_to_db(spectrogram: &[Vec<SignalStrength>], floor_db: f32) -> Vec<Vec<f32>> {
+    spectrogram.iter().map(|frame| conversion::power_to_db(frame, floor_db)).collect()
+}
+
+/// Converts a spectrogram into a row-major 8-bit grayscale image.
+/// Each frame becomes a column, with the highest bin in the top row.
+/// Magnitudes are scaled to decibels relative to the loudest bin, and anything at or below `db_floor` maps to 0.
+/// Returns the image buffer along with its width and height.
+pub fn spectrogram_to_image(spectrogram: &[Vec<SignalStrength>], db_floor: f32) -> Result<(Vec<u8>, usize, usize), Error> {
+    if !(db_floor < 0.0) { Err(Error::DecibelFloor)? }
+
+    let width = spectrogram.len();
+    let height = spectrogram.first().map(|frame| frame.len()).unwrap_or(0);
+
+    for frame in spectrogram {
+        if frame.len() != height { Err(Error::FrameLength(height, frame.len()))? }
+    }
+
+    let max_power = spectrogram.iter().flatten().cloned().fold(0.0, SignalStrength::max);
+
+    let mut image = vec![0u8; width * height];
+
+    // A completely silent spectrogram has no reference level, and is left black.
+    if max_power > 0.0 {
+        for (x, frame) in spectrogram.iter().enumerate() {
+            for (i, power) in frame.iter().enumerate() {
+                let db = 10.0 * (power / max_power).log10();
+                let level = (1.0 - db.max(db_floor) / db_floor) * 255.0;
+
+                let y = height - 1 - i;
+                image[y * width + x] = level.round() as u8;
+            }
+        }
+    }
+
+    Ok((image, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestUtil;
+    use crate::window_kind::WindowKind;
+
+    #[test]
+    fn test_frame_count() {
+        const FFT_LEN: usize = 64;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        for &input_len in &[0, 63, 64, 65, 100, 128, 1000] {
+            for &hop in &[1, 16, 64, 100] {
+                let samples = TestUtil::generate_wave_samples(44100, 440.0, input_len);
+
+                let expected = spectrogram(&mut analyzer, &samples, hop).unwrap().len();
+                let produced = frame_count(input_len, FFT_LEN, hop).unwrap();
+
+                assert_eq!(expected, produced);
+            }
+        }
+
+        assert_eq!(Err(Error::HopSize), frame_count(100, FFT_LEN, 0));
+    }
+
+    #[test]
+    fn test_spectrogram() {
+        const FFT_LEN: usize = 64;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let samples = TestUtil::generate_wave_samples(44100, 440.0, 200);
+
+        let produced = spectrogram(&mut analyzer, &samples, 32).unwrap();
+        assert_eq!(5, produced.len());
+
+        // Each frame matches analyzing that window of samples directly.
+        let frame = SampleBuffer::from(samples[64..128].to_vec());
+        let (expected, _) = analyzer.analyze(&frame).unwrap();
+        assert_eq!(expected, produced[2].as_slice());
+    }
+
+    #[test]
+    fn test_coherence() {
+        const FFT_LEN: usize = 256;
+        const LEN: usize = 16384;
+
+        let mut analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+
+        let noise = TestUtil::generate_noise_samples(2 * LEN);
+        let (x, independent) = noise.split_at(LEN);
+
+        let produced = coherence(x, x, &mut analyzer, FFT_LEN / 2).unwrap();
+        assert_eq!(FFT_LEN, produced.len());
+        for c in &produced {
+            assert_approx_eq!(1.0, c, 1e-3);
+        }
+
+        let produced = coherence(x, independent, &mut analyzer, FFT_LEN / 2).unwrap();
+        let mean = produced.iter().sum::<f32>() / produced.len() as f32;
+        assert!(mean < 0.05);
+
+        assert_eq!(Err(Error::NumSamples(LEN, 4)), coherence(x, &[0.0; 4], &mut analyzer, 1));
+        assert_eq!(Err(Error::TooFewSamples(FFT_LEN, 4)), coherence(&[0.0; 4], &[0.0; 4], &mut analyzer, 1));
+    }
+
+    #[test]
+    fn test_spectrogram_to_db() {
+        let mut analyzer = Analyzer::new(64, WindowKind::Hanning);
+
+        let samples = TestUtil::generate_wave_samples(44100, 440.0, 200);
+        let frames = spectrogram(&mut analyzer, &samples, 32).unwrap();
+
+        let produced = spectrogram_to_db(&frames, -80.0);
+
+        assert_eq!(frames.len(), produced.len());
+        for (frame, p) in frames.iter().zip(produced) {
+            assert_eq!(conversion::power_to_db(frame, -80.0), p);
+        }
+    }
+
+    #[test]
+    fn test_spectrogram_to_image() {
+        let spectrogram = vec![
+            vec![1.0, 0.1, 0.01, 0.001],
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.5, 1.0, 0.0, 0.25],
+        ];
+
+        let (image, width, height) = spectrogram_to_image(&spectrogram, -20.0).unwrap();
+
+        assert_eq!(3, width);
+        assert_eq!(4, height);
+        assert_eq!(width * height, image.len());
+
+        // The silent column is completely black.
+        for y in 0..height {
+            assert_eq!(0, image[y * width + 1]);
+        }
+
+        // The loudest bins are white, and the lowest bin is on the bottom row.
+        assert_eq!(255, image[(height - 1) * width]);
+        assert_eq!(255, image[(height - 2) * width + 2]);
+
+        // Bins at or below the floor are black.
+        assert_eq!(0, image[width]);
+        assert_eq!(0, image[0]);
+
+        // Bins in between are scaled linearly in decibels.
+        assert_eq!(178, image[2]);
+
+        assert_eq!(Err(Error::DecibelFloor), spectrogram_to_image(&spectrogram, 0.0));
+        assert_eq!(Err(Error::FrameLength(4, 2)), spectrogram_to_image(&[vec![0.0; 4], vec![0.0; 2]], -20.0));
+    }
+}
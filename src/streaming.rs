@@ -0,0 +1,525 @@
+//! Analyzes a continuous stream of samples, emitting a frame every hop.
+
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread::Builder as ThreadBuilder;
+
+use crate::Error;
+use crate::sample::Sample;
+use crate::sample::SampleBuffer;
+use crate::analyzer::Analyzer;
+use crate::buckets::Buckets;
+use crate::types::SignalStrength;
+
+pub struct StreamingAnalyzer {
+    analyzer: Analyzer,
+    buckets: Buckets,
+    sampling_rate: usize,
+    hop: usize,
+
+    // Holds the most recent frame's worth of samples.
+    sample_buffer: SampleBuffer,
+
+    // Number of sample pairs pushed since the last emitted frame.
+    since_last_frame: usize,
+
+    // Samples pushed while paused are dropped.
+    paused: bool,
+
+    // Set on resume, so that no frames are emitted until the buffer holds only post-resume samples.
+    awaiting_prime: bool,
+
+    // A left sample from the end of the last push, waiting for its right sample.
+    pending: Option<Sample>,
+
+    // Factor applied to each frame's spectra so that overlapping frames sum to the signal energy, if enabled.
+    energy_scale: Option<SignalStrength>,
+
+    // Preallocated spectra for the scaled output, so that `push_with` still does not allocate.
+    scaled_l: Vec<SignalStrength>,
+    scaled_r: Vec<SignalStrength>,
+}
+
+impl StreamingAnalyzer {
+    pub fn new(analyzer: Analyzer, buckets: Buckets, sampling_rate: usize, hop: usize) -> Result<Self, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+        if !(hop > 0) { Err(Error::HopSize)? }
+
+        let sample_buffer = SampleBuffer::new(analyzer.len());
+
+        Ok(Self {
+            analyzer,
+            buckets,
+            sampling_rate,
+            hop,
+            sample_buffer,
+            since_last_frame: 0,
+            paused: false,
+            awaiting_prime: false,
+            pending: None,
+            energy_scale: None,
+            scaled_l: Vec::new(),
+            scaled_r: Vec::new(),
+        })
+    }
+
+    /// Scales each frame's spectra by the hop over the window's energy, so that summing the bins of every emitted frame
+    /// gives the energy of the signal per channel, however much the frames overlap.
+    /// This follows from Parseval's theorem, and assumes the analyzer does not use amplitude normalization.
+    pub fn with_energy_normalization(mut self, normalize: bool) -> Self {
+        let len = self.analyzer.len();
+
+        self.energy_scale =
+            if normalize {
+                let window_energy: SignalStrength = self.analyzer.window_coefficients().iter().map(|w| w * w).sum();
+                Some(self.hop as SignalStrength / (len as SignalStrength * window_energy))
+            }
+            else { None }
+        ;
+
+        self.scaled_l = vec![0.0; if normalize { len } else { 0 }];
+        self.scaled_r = vec![0.0; if normalize { len } else { 0 }];
+
+        self
+    }
+
+    #[inline]
+    pub fn is_energy_normalized(&self) -> bool {
+        self.energy_scale.is_some()
+    }
+
+    #[inline]
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    #[inline]
+    pub fn buckets_len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses analysis, dropping any samples pushed until `resume` is called.
+    /// The position within the current hop is kept, so frames stay aligned to the same hop grid afterwards.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes analysis after a pause.
+    /// Samples from before the pause are discarded, and no frames are emitted until a full frame of new samples has been pushed.
+    pub fn resume(&mut self) {
+        if !self.paused { return }
+
+        self.paused = false;
+        self.sample_buffer = SampleBuffer::new(self.analyzer.len());
+        self.awaiting_prime = true;
+        self.pending = None;
+    }
+
+    fn for_each_frame<F>(&mut self, samples: &[Sample], mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&Buckets, &[SignalStrength], &[SignalStrength]) -> Result<(), Error>,
+    {
+        if self.paused { return Ok(()) }
+
+        let mut samples = samples;
+
+        // Samples are interleaved stereo, so a push may end partway through a pair, which the next push completes.
+        if let Some(left) = self.pending.take() {
+            match samples.split_first() {
+                None => { self.pending = Some(left); },
+                Some((&right, rest)) => {
+                    self.push_pairs(&[left, right], &mut f)?;
+                    samples = rest;
+                },
+            }
+        }
+
+        let num_whole = samples.len() - samples.len() % 2;
+        self.pending = samples.get(num_whole).copied();
+
+        self.push_pairs(&samples[..num_whole], &mut f)
+    }
+
+    // Pushes whole interleaved pairs, calling `f` for each emitted frame.
+    fn push_pairs<F>(&mut self, pairs: &[Sample], f: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Buckets, &[SignalStrength], &[SignalStrength]) -> Result<(), Error>,
+    {
+        let mut remaining = pairs;
+
+        while !remaining.is_empty() {
+            // Only push up to the next frame boundary.
+            let needed = (self.hop - self.since_last_frame) * 2;
+            let (chunk, rest) = remaining.split_at(needed.min(remaining.len()));
+
+            self.sample_buffer.push_interleaved(chunk);
+            self.since_last_frame += chunk.len() / 2;
+            remaining = rest;
+
+            if self.since_last_frame == self.hop {
+                self.since_last_frame = 0;
+
+                // A frame straddling a pause would mix samples from either side of the gap.
+                if self.awaiting_prime {
+                    if !self.sample_buffer.is_primed() { continue }
+                    self.awaiting_prime = false;
+                }
+
+                let (spectrum_l, spectrum_r) = self.analyzer.analyze(&self.sample_buffer)?;
+
+                match self.energy_scale {
+                    None => f(&self.buckets, spectrum_l, spectrum_r)?,
+                    Some(scale) => {
+                        for (o, s) in self.scaled_l.iter_mut().zip(spectrum_l) { *o = s * scale; }
+                        for (o, s) in self.scaled_r.iter_mut().zip(spectrum_r) { *o = s * scale; }
+
+                        f(&self.buckets, &self.scaled_l, &self.scaled_r)?;
+                    },
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes a slice of interleaved samples, calling `f` with the left and right spectra of each emitted frame.
+    /// Unlike the other push methods, this does not allocate.
+    pub fn push_with<F>(&mut self, samples: &[Sample], mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[SignalStrength], &[SignalStrength]),
+    {
+        self.for_each_frame(samples, |_, spectrum_l, spectrum_r| {
+            f(spectrum_l, spectrum_r);
+            Ok(())
+        })
+    }
+
+    /// Pushes a slice of interleaved samples, returning the bucketized output of each emitted frame.
+    /// The left and right spectra are averaged before bucketizing.
+    pub fn push_bucketized(&mut self, samples: &[Sample]) -> Result<Vec<Vec<SignalStrength>>, Error> {
+        let mut frames = Vec::new();
+
+        let sampling_rate = self.sampling_rate;
+        let mut mono = vec![0.0; self.analyzer.len()];
+
+        self.for_each_frame(samples, |buckets, spectrum_l, spectrum_r| {
+            for (m, (l, r)) in mono.iter_mut().zip(spectrum_l.iter().zip(spectrum_r)) {
+                *m = (l + r) / 2.0;
+            }
+
+            frames.push(buckets.bucketize(&mono, sampling_rate)?);
+            Ok(())
+        })?;
+
+        Ok(frames)
+    }
+}
+
+/// The sending end for samples and the receiving end for spectra of a worker started by `spawn_worker`.
+pub type WorkerChannels = (Sender<Vec<Sample>>, Receiver<Result<Vec<SignalStrength>, Error>>);
+
+/// Spawns a thread that pushes the interleaved stereo samples sent to it through `streaming`, so that capture and analysis can run separately.
+/// For each emitted frame, the average of its left and right spectra is sent back.
+/// If a frame fails to analyze, the error is sent back instead and the thread exits.
+/// It also exits once the sample sender or the spectrum receiver is dropped.
+/// Fails with `Error::ThreadSpawn` if the operating system cannot start the thread.
+pub fn spawn_worker(mut streaming: StreamingAnalyzer) -> Result<WorkerChannels, Error> {
+    let (sample_tx, sample_rx) = mpsc::channel::<Vec<Sample>>();
+    let (spectrum_tx, spectrum_rx) = mpsc::channel();
+
+    ThreadBuilder::new()
+        .name("voxane-analysis".to_string())
+        .spawn(move || {
+            let mut disconnected = false;
+
+            for samples in sample_rx {
+                let pushed = streaming.push_with(&samples, |spectrum_l, spectrum_r| {
+                    if disconnected { return }
+
+                    let spectrum = spectrum_l.iter().zip(spectrum_r).map(|(l, r)| (l + r) / 2.0).collect();
+                    disconnected = spectrum_tx.send(Ok(spectrum)).is_err();
+                });
+
+                if let Err(err) = pushed {
+                    let _ = spectrum_tx.send(Err(err));
+                    return
+                }
+
+                if disconnected { return }
+            }
+        })
+        .map_err(|_| Error::ThreadSpawn)?
+    ;
+
+    Ok((sample_tx, spectrum_rx))
+}
+
+/// Collects incoming samples into complete, non-overlapping frames of a fixed length.
+pub struct FrameAccumulator {
+    len: usize,
+
+    // Samples left over after the last complete frame.
+    pending: Vec<Sample>,
+}
+
+impl FrameAccumulator {
+    pub fn new(len: usize) -> Result<Self, Error> {
+        if !(len > 0) { Err(Error::FrameSize)? }
+
+        Ok(Self {
+            len,
+            pending: Vec::with_capacity(len),
+        })
+    }
+
+    #[inline]
+    pub fn frame_len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of samples waiting for the next frame to fill.
+    #[inline]
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Pushes a slice of samples, returning each frame completed by them.
+    /// Any remaining samples are kept for the next push.
+    pub fn push(&mut self, samples: &[Sample]) -> Vec<Vec<Sample>> {
+        let mut frames = Vec::new();
+        let mut remaining = samples;
+
+        while !remaining.is_empty() {
+            let needed = self.len - self.pending.len();
+            let (chunk, rest) = remaining.split_at(needed.min(remaining.len()));
+
+            self.pending.extend_from_slice(chunk);
+            remaining = rest;
+
+            if self.pending.len() == self.len {
+                frames.push(std::mem::replace(&mut self.pending, Vec::with_capacity(self.len)));
+            }
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::analyzer::NonFinitePolicy;
+    use crate::test_util::TestUtil;
+    use crate::window_kind::WindowKind;
+
+    const SAMPLES_PER_PERIOD: usize = 44100;
+    const FFT_LEN: usize = 256;
+    const HOP: usize = 64;
+
+    fn interleaved_wave(len: usize) -> Vec<Sample> {
+        TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 440.0, len)
+        .into_iter()
+        .flat_map(|s| vec![s, s])
+        .collect()
+    }
+
+    #[test]
+    fn test_push_with() {
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        let mut streaming = StreamingAnalyzer::new(analyzer, buckets, SAMPLES_PER_PERIOD, HOP).unwrap();
+
+        let mut num_calls = 0;
+        for chunk in interleaved_wave(1000).chunks(150) {
+            streaming.push_with(chunk, |spectrum_l, spectrum_r| {
+                assert_eq!(FFT_LEN, spectrum_l.len());
+                assert_eq!(FFT_LEN, spectrum_r.len());
+                num_calls += 1;
+            }).unwrap();
+        }
+
+        assert_eq!(1000 / HOP, num_calls);
+    }
+
+    #[test]
+    fn test_push_bucketized() {
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        let mut streaming = StreamingAnalyzer::new(analyzer, buckets, SAMPLES_PER_PERIOD, HOP).unwrap();
+
+        // Push in uneven chunks to exercise the hop boundary handling.
+        let mut produced = Vec::new();
+        for chunk in interleaved_wave(1000).chunks(150) {
+            produced.extend(streaming.push_bucketized(chunk).unwrap());
+        }
+
+        assert_eq!(1000 / HOP, produced.len());
+        for frame in produced {
+            assert_eq!(streaming.buckets_len(), frame.len());
+        }
+    }
+
+    #[test]
+    fn test_odd_length_pushes() {
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        // The channels differ, so that swapping them would change the output.
+        let left = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 440.0, 1000);
+        let right = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 3000.0, 1000);
+        let interleaved: Vec<_> = left.iter().zip(&right).flat_map(|(&l, &r)| vec![l, r]).collect();
+
+        let collect = |chunk_len: usize| {
+            let mut streaming = StreamingAnalyzer::new(analyzer.clone(), buckets.clone(), SAMPLES_PER_PERIOD, HOP).unwrap();
+
+            let mut produced = Vec::new();
+            for chunk in interleaved.chunks(chunk_len) {
+                streaming.push_with(chunk, |spectrum_l, spectrum_r| produced.push((spectrum_l.to_vec(), spectrum_r.to_vec()))).unwrap();
+            }
+            produced
+        };
+
+        let expected = collect(150);
+        assert_eq!(1000 / HOP, expected.len());
+
+        // Chunks ending partway through a pair, including single samples, give the same frames.
+        assert_eq!(expected, collect(151));
+        assert_eq!(expected, collect(1));
+    }
+
+    #[test]
+    fn test_energy_normalization() {
+        const LEN: usize = 65536;
+
+        let noise = TestUtil::generate_noise_samples(LEN);
+        let interleaved: Vec<_> = noise.iter().flat_map(|&s| vec![s, s]).collect();
+        let expected: f32 = noise.iter().map(|s| s * s).sum();
+
+        let total_energy = |hop: usize, normalize: bool| {
+            let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+            let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+            let mut streaming = StreamingAnalyzer::new(analyzer, buckets, SAMPLES_PER_PERIOD, hop).unwrap().with_energy_normalization(normalize);
+            assert_eq!(normalize, streaming.is_energy_normalized());
+
+            let mut total = 0.0;
+            streaming.push_with(&interleaved, |spectrum_l, _| total += spectrum_l.iter().sum::<f32>()).unwrap();
+            total
+        };
+
+        // Without overlap, with 50% overlap, and with 75% overlap.
+        for &hop in &[FFT_LEN, FFT_LEN / 2, FFT_LEN / 4] {
+            assert_approx_eq!(1.0, total_energy(hop, true) / expected, 0.05);
+        }
+
+        // Without normalization, halving the hop counts the same energy twice.
+        assert_approx_eq!(2.0, total_energy(FFT_LEN / 2, false) / total_energy(FFT_LEN, false), 0.1);
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        let mut streaming = StreamingAnalyzer::new(analyzer.clone(), buckets, SAMPLES_PER_PERIOD, HOP).unwrap();
+
+        let mut num_calls = 0;
+        streaming.push_with(&interleaved_wave(500), |_, _| num_calls += 1).unwrap();
+        assert_eq!(500 / HOP, num_calls);
+
+        // Samples pushed while paused are ignored.
+        streaming.pause();
+        assert!(streaming.is_paused());
+
+        let mut num_calls = 0;
+        streaming.push_with(&interleaved_wave(300), |_, _| num_calls += 1).unwrap();
+        assert_eq!(0, num_calls);
+
+        streaming.resume();
+        assert!(!streaming.is_paused());
+
+        // The hop boundaries that fall before a full frame of new samples has arrived are skipped.
+        let resumed = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 1000.0, 300);
+        let interleaved: Vec<_> = resumed.iter().flat_map(|&s| vec![s, s]).collect();
+
+        let mut num_calls = 0;
+        streaming.push_with(&interleaved[..400], |_, _| num_calls += 1).unwrap();
+        assert_eq!(0, num_calls);
+
+        // The next frame keeps the original hop alignment, landing 12 samples after the resume plus a whole number of hops.
+        let boundary = HOP - 500 % HOP + 4 * HOP;
+
+        let mut analyzer = analyzer;
+        let expected = analyzer.analyze(&SampleBuffer::from(resumed[boundary - FFT_LEN..boundary].to_vec())).unwrap().0.to_vec();
+
+        let mut produced = Vec::new();
+        streaming.push_with(&interleaved[400..], |spectrum_l, _| produced.push(spectrum_l.to_vec())).unwrap();
+        assert_eq!(vec![expected], produced);
+    }
+
+    #[test]
+    fn test_spawn_worker() {
+        let analyzer = Analyzer::new(FFT_LEN, WindowKind::Hanning);
+        let buckets = Buckets::new(20.0, 20000.0, 12).unwrap();
+
+        let streaming = StreamingAnalyzer::new(analyzer.clone(), buckets.clone(), SAMPLES_PER_PERIOD, HOP).unwrap();
+        let (sender, receiver) = spawn_worker(streaming).unwrap();
+
+        // Chunks ending partway through a pair are carried over, as with pushing directly.
+        let samples = interleaved_wave(1000);
+        for chunk in samples.chunks(151) {
+            sender.send(chunk.to_vec()).unwrap();
+        }
+
+        // Dropping the sender lets the worker finish, which ends the spectra.
+        drop(sender);
+        let produced: Vec<_> = receiver.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(1000 / HOP, produced.len());
+
+        // The last spectrum covers the frame ending on the last hop boundary, with both channels equal.
+        let end = 1000 / HOP * HOP;
+        let mut plain = analyzer;
+        let frame = SampleBuffer::from(TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 440.0, end)[end - FFT_LEN..].to_vec());
+        let (expected, _) = plain.analyze(&frame).unwrap();
+        assert_eq!(expected, produced.last().unwrap().as_slice());
+
+        // A frame that fails to analyze is reported, and stops the worker.
+        let strict = Analyzer::new(FFT_LEN, WindowKind::Hanning).with_non_finite_policy(NonFinitePolicy::Strict);
+        let streaming = StreamingAnalyzer::new(strict, buckets, SAMPLES_PER_PERIOD, HOP).unwrap();
+        let (sender, receiver) = spawn_worker(streaming).unwrap();
+
+        sender.send(vec![Sample::NAN; HOP * 2]).unwrap();
+        assert_eq!(Err(Error::NonFiniteInput), receiver.recv().unwrap());
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn test_frame_accumulator() {
+        let mut accumulator = FrameAccumulator::new(FFT_LEN).unwrap();
+
+        let samples = TestUtil::generate_wave_samples(SAMPLES_PER_PERIOD, 440.0, FFT_LEN * 3 / 2);
+
+        let produced = accumulator.push(&samples);
+        assert_eq!(vec![samples[..FFT_LEN].to_vec()], produced);
+        assert_eq!(FFT_LEN / 2, accumulator.pending_len());
+
+        // The remainder is the start of the next frame.
+        let more = vec![0.5; FFT_LEN];
+        let produced = accumulator.push(&more);
+        assert_eq!(1, produced.len());
+        assert_eq!(&samples[FFT_LEN..], &produced[0][..FFT_LEN / 2]);
+        assert_eq!(&more[..FFT_LEN / 2], &produced[0][FFT_LEN / 2..]);
+
+        assert!(accumulator.push(&[]).is_empty());
+        assert!(FrameAccumulator::new(0).is_err());
+    }
+}
@@ -9,4 +9,17 @@ impl TestUtil {
     pub fn generate_wave_samples(samples_per_period: usize, frequency: Frequency, len: usize) -> Vec<Sample> {
         WaveGen::new(WaveFunction::Sine, samples_per_period, frequency).take(len).collect()
     }
+
+    /// Generates deterministic white noise in the range [-0.25, 0.25).
+    pub fn generate_noise_samples(len: usize) -> Vec<Sample> {
+        // Xorshift, so that results are repeatable across runs.
+        let mut state: u32 = 0x1234_5678;
+
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 - 0.5) * 0.5
+        }).collect()
+    }
 }
@@ -0,0 +1,624 @@
+//! Stateful helpers that follow a spectral feature over time.
+
+use std::collections::VecDeque;
+
+use crate::Error;
+use crate::analyzer::Analyzer;
+use crate::types::Frequency;
+use crate::types::SignalStrength;
+
+/// The smoothed centroid of a `CentroidTracker` at a point in time, independent of its settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CentroidTrackerSnapshot {
+    centroid: Option<Frequency>,
+}
+
+pub struct CentroidTracker {
+    analyzer: Analyzer,
+    sampling_rate: usize,
+    smoothing: f32,
+
+    // Smoothed centroid, unset until the first spectrum is seen.
+    centroid: Option<Frequency>,
+}
+
+impl CentroidTracker {
+    /// Creates a new tracker.
+    /// A `smoothing` of zero follows the centroid exactly, values closer to one respond more slowly.
+    pub fn new(analyzer: Analyzer, sampling_rate: usize, smoothing: f32) -> Result<Self, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+        if !(0.0..1.0).contains(&smoothing) { Err(Error::SmoothingFactor)? }
+
+        Ok(Self {
+            analyzer,
+            sampling_rate,
+            smoothing,
+            centroid: None,
+        })
+    }
+
+    /// Pushes a new spectrum, returning the updated smoothed centroid.
+    pub fn push(&mut self, spectrum: &[SignalStrength]) -> Result<Frequency, Error> {
+        let current = self.analyzer.spectral_centroid(spectrum, self.sampling_rate)?;
+
+        let smoothed = match self.centroid {
+            None => current,
+            Some(prev) => self.smoothing * prev + (1.0 - self.smoothing) * current,
+        };
+
+        self.centroid = Some(smoothed);
+
+        Ok(smoothed)
+    }
+
+    /// Captures the current smoothed centroid.
+    pub fn snapshot(&self) -> CentroidTrackerSnapshot {
+        CentroidTrackerSnapshot { centroid: self.centroid }
+    }
+
+    /// Restores a previously captured centroid, keeping this tracker's own settings.
+    pub fn restore(&mut self, snapshot: CentroidTrackerSnapshot) {
+        self.centroid = snapshot.centroid;
+    }
+}
+
+/// Follows the dominant frequency of successive spectra for a stable pitch readout.
+/// Frames with a low confidence are ignored, and the rest go through a median filter to reject octave jumps before smoothing.
+pub struct PitchTracker {
+    sampling_rate: usize,
+    window_len: usize,
+    min_confidence: f32,
+    smoothing: f32,
+
+    // The most recent confident frequencies, oldest first.
+    history: VecDeque<Frequency>,
+
+    // Smoothed pitch, unset until the first confident frame is seen.
+    pitch: Option<Frequency>,
+}
+
+impl PitchTracker {
+    /// Creates a new tracker, taking the median over the last `window_len` confident frames.
+    /// A frame is confident if its dominant bin holds at least `min_confidence` of the total power.
+    /// A `smoothing` of zero follows the median exactly, values closer to one respond more slowly.
+    pub fn new(sampling_rate: usize, window_len: usize, min_confidence: f32, smoothing: f32) -> Result<Self, Error> {
+        if !(sampling_rate > 0) { Err(Error::SamplingRate(sampling_rate))? }
+        if !(window_len > 0) { Err(Error::WindowLength)? }
+        if !(0.0..1.0).contains(&smoothing) { Err(Error::SmoothingFactor)? }
+
+        Ok(Self {
+            sampling_rate,
+            window_len,
+            min_confidence,
+            smoothing,
+            history: VecDeque::with_capacity(window_len),
+            pitch: None,
+        })
+    }
+
+    #[inline]
+    pub fn window_len(&self) -> usize {
+        self.window_len
+    }
+
+    #[inline]
+    pub fn min_confidence(&self) -> f32 {
+        self.min_confidence
+    }
+
+    /// Pushes a new spectrum produced by `analyzer`, returning the tracked pitch.
+    /// No pitch is tracked until the first confident frame.
+    pub fn push(&mut self, analyzer: &Analyzer, spectrum: &[SignalStrength]) -> Result<Option<Frequency>, Error> {
+        let dominant = analyzer.dominant_frequency(spectrum, self.sampling_rate)?;
+
+        let frequency = match dominant {
+            Some((frequency, confidence)) if confidence >= self.min_confidence => frequency,
+            _ => return Ok(self.pitch),
+        };
+
+        if self.history.len() == self.window_len { self.history.pop_front(); }
+        self.history.push_back(frequency);
+
+        let mut sorted: Vec<_> = self.history.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = (sorted[(sorted.len() - 1) / 2] + sorted[sorted.len() / 2]) / 2.0;
+
+        let smoothed = match self.pitch {
+            None => median,
+            Some(prev) => self.smoothing * prev + (1.0 - self.smoothing) * median,
+        };
+
+        self.pitch = Some(smoothed);
+
+        Ok(self.pitch)
+    }
+}
+
+/// Fires when the energy in a frequency band rises past a threshold, such as to react to kick drum hits.
+pub struct BandTrigger {
+    fft_len: usize,
+
+    // Valid FFT bins whose frequencies are within the band.
+    bins: std::ops::RangeInclusive<usize>,
+
+    threshold: SignalStrength,
+    release: SignalStrength,
+
+    // Whether the band has fallen below the release level since the last trigger.
+    armed: bool,
+}
+
+impl BandTrigger {
+    /// Creates a new trigger for the band from `lower_cutoff` up to but excluding `upper_cutoff`.
+    /// After firing, the band energy must fall below `threshold * (1 - hysteresis)` before it can fire again.
+    /// The band must contain at least one valid FFT bin.
+    pub fn new(analyzer: &Analyzer, sampling_rate: usize, lower_cutoff: Frequency, upper_cutoff: Frequency, threshold: SignalStrength, hysteresis: f32) -> Result<Self, Error> {
+        if !(upper_cutoff > 0.0) { Err(Error::UpperCutoff)? }
+        if !(lower_cutoff > 0.0) { Err(Error::LowerCutoff)? }
+        if !(lower_cutoff < upper_cutoff) { Err(Error::CutoffOrder)? }
+        if !(threshold > 0.0) { Err(Error::TriggerFactor)? }
+        if !(0.0..1.0).contains(&hysteresis) { Err(Error::Hysteresis)? }
+
+        let fft_bin_size = analyzer.fft_bin_size(sampling_rate)?;
+
+        let first = ((lower_cutoff / fft_bin_size).ceil() as usize).max(1);
+        let last = ((upper_cutoff / fft_bin_size).ceil() as usize).saturating_sub(1).min(analyzer.len() / 2);
+
+        // A band narrower than a bin, or above the Nyquist frequency, would never fire.
+        if first > last { Err(Error::EmptyBand)? }
+
+        Ok(Self {
+            fft_len: analyzer.len(),
+            bins: first..=last,
+            threshold,
+            release: threshold * (1.0 - hysteresis),
+            armed: true,
+        })
+    }
+
+    /// Pushes a new spectrum, returning true if the band energy has just risen past the threshold.
+    pub fn push(&mut self, spectrum: &[SignalStrength]) -> Result<bool, Error> {
+        if self.fft_len != spectrum.len() { Err(Error::SpectrumLength(self.fft_len, spectrum.len()))? }
+
+        let energy: SignalStrength = self.bins.clone().map(|i| spectrum[i]).sum();
+
+        let triggered = self.armed && energy > self.threshold;
+
+        if triggered { self.armed = false; }
+        else if energy < self.release { self.armed = true; }
+
+        Ok(triggered)
+    }
+}
+
+/// Measures how much the bucket output changes from one frame to the next.
+#[derive(Default)]
+pub struct MotionMeter {
+    previous: Vec<SignalStrength>,
+}
+
+impl MotionMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes a new frame of buckets, returning the mean absolute change per bucket since the last frame.
+    /// The first frame, and any frame with a different number of buckets, has no motion.
+    pub fn push(&mut self, buckets: &[SignalStrength]) -> f32 {
+        let motion =
+            if self.previous.len() != buckets.len() || buckets.is_empty() { 0.0 }
+            else {
+                let total: f32 = self.previous.iter().zip(buckets).map(|(p, b)| (b - p).abs()).sum();
+                total / buckets.len() as f32
+            }
+        ;
+
+        self.previous.clear();
+        self.previous.extend_from_slice(buckets);
+
+        motion
+    }
+}
+
+/// Flags sudden jumps in energy from one frame to the next, as a simpler alternative to spectral flux.
+pub struct TransientDetector {
+    factor: f32,
+    threshold: f32,
+
+    // Moving average of the frame energy, unset until the first frame is seen.
+    average: Option<SignalStrength>,
+
+    // Whether the last frame was above the threshold, so that a sustained burst is only flagged once.
+    above: bool,
+}
+
+impl TransientDetector {
+    /// Creates a new detector, flagging frames whose energy is more than `threshold` times the moving average.
+    /// After `time_constant` seconds, the average has covered about 63% of a step in energy.
+    pub fn new(time_constant: f32, frame_rate: f32, threshold: f32) -> Result<Self, Error> {
+        if !(time_constant > 0.0) { Err(Error::TimeConstant)? }
+        if !(frame_rate > 0.0) { Err(Error::FrameRate)? }
+        if !(threshold > 0.0) { Err(Error::TriggerFactor)? }
+
+        Ok(Self {
+            factor: (-1.0 / (time_constant * frame_rate)).exp(),
+            threshold,
+            average: None,
+            above: false,
+        })
+    }
+
+    #[inline]
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Pushes a new frame of power values, such as a spectrum or buckets, returning true if it starts a transient.
+    /// Frames stay above the threshold until the average catches up, but only the first of them is flagged.
+    pub fn push(&mut self, frame: &[SignalStrength]) -> bool {
+        let energy: SignalStrength = frame.iter().sum();
+
+        let average = self.average.unwrap_or(energy);
+        let above = energy > self.threshold * average;
+
+        let is_transient = above && !self.above;
+
+        self.above = above;
+        self.average = Some(self.factor * average + (1.0 - self.factor) * energy);
+
+        is_transient
+    }
+}
+
+/// Keeps the most recent frames, such as spectra or buckets, to measure spectral flux across any lag.
+/// Comparing a frame against one a beat period earlier gives low flux, which helps with beat period estimation.
+pub struct FluxHistory {
+    capacity: usize,
+
+    // The most recent frames, oldest first.
+    frames: VecDeque<Vec<SignalStrength>>,
+}
+
+impl FluxHistory {
+    /// Creates a new history keeping up to `capacity` frames, supporting lags of up to one less than that.
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        if !(capacity > 0) { Err(Error::WindowLength)? }
+
+        Ok(Self {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+        })
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of frames currently held.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Pushes a new frame, dropping the oldest one if the history is full.
+    pub fn push(&mut self, frame: &[SignalStrength]) {
+        let mut recycled = if self.frames.len() == self.capacity { self.frames.pop_front().unwrap() } else { Vec::new() };
+
+        recycled.clear();
+        recycled.extend_from_slice(frame);
+
+        self.frames.push_back(recycled);
+    }
+
+    /// Calculates the spectral flux of the newest frame against the one `lag` frames before it,
+    /// which is the summed increase of each value between them.
+    /// There is no flux for a lag of zero, a lag reaching past the oldest frame, or frames of different lengths.
+    pub fn flux_at_lag(&self, lag: usize) -> Option<f32> {
+        if lag == 0 { return None }
+
+        let newest = self.frames.back()?;
+        let earlier = self.frames.get(self.frames.len().checked_sub(lag + 1)?)?;
+
+        if newest.len() != earlier.len() { return None }
+
+        Some(newest.iter().zip(earlier).map(|(n, e)| (n - e).max(0.0)).sum())
+    }
+}
+
+// Sparkline levels, from lowest to highest.
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Records the value of a single band over the most recent frames.
+pub struct BandHistory {
+    band_index: usize,
+    capacity: usize,
+
+    // The most recent band values, oldest first.
+    history: VecDeque<SignalStrength>,
+}
+
+impl BandHistory {
+    /// Creates a new history of the band at `band_index`, keeping up to `capacity` frames.
+    pub fn new(band_index: usize, capacity: usize) -> Result<Self, Error> {
+        if !(capacity > 0) { Err(Error::WindowLength)? }
+
+        Ok(Self {
+            band_index,
+            capacity,
+            history: VecDeque::with_capacity(capacity),
+        })
+    }
+
+    #[inline]
+    pub fn band_index(&self) -> usize {
+        self.band_index
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns an iterator over the recorded values, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &SignalStrength> {
+        self.history.iter()
+    }
+
+    /// Pushes a new frame of buckets, recording the value of this history's band.
+    pub fn push(&mut self, buckets: &[SignalStrength]) -> Result<(), Error> {
+        let value = *buckets.get(self.band_index).ok_or(Error::BucketIndex(self.band_index, buckets.len()))?;
+
+        if self.history.len() == self.capacity { self.history.pop_front(); }
+        self.history.push_back(value);
+
+        Ok(())
+    }
+
+    /// Renders up to the last `width` values as a unicode sparkline, oldest on the left.
+    /// Values are scaled between the lowest and highest shown, and a flat history renders at the lowest level.
+    pub fn render_sparkline(&self, width: usize) -> String {
+        let shown = self.history.iter().skip(self.history.len().saturating_sub(width));
+
+        let (min, max) =
+            shown.clone()
+            .fold((SignalStrength::INFINITY, SignalStrength::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)))
+        ;
+
+        let top = (SPARK_CHARS.len() - 1) as SignalStrength;
+
+        shown
+        .map(|&v| {
+            let level = if max > min { ((v - min) / (max - min) * top).round() } else { 0.0 };
+            SPARK_CHARS[level as usize]
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLING_RATE: usize = 8;
+    const FFT_LEN: usize = 8;
+
+    #[test]
+    fn test_push() {
+        let analyzer = Analyzer::new(FFT_LEN, Default::default());
+        let mut tracker = CentroidTracker::new(analyzer, SAMPLING_RATE, 0.5).unwrap();
+
+        // Energy only in bin 1 (1 Hz), then only in bin 3 (3 Hz).
+        let low = vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        let high = vec![0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0];
+
+        assert_eq!(1.0, tracker.push(&low).unwrap());
+
+        let mut prev_distance = f32::INFINITY;
+        for _ in 0..16 {
+            let distance = (3.0 - tracker.push(&high).unwrap()).abs();
+            assert!(distance < prev_distance);
+            prev_distance = distance;
+        }
+
+        assert!(prev_distance < 0.001);
+
+        let analyzer = Analyzer::new(FFT_LEN, Default::default());
+        assert!(CentroidTracker::new(analyzer, SAMPLING_RATE, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let analyzer = Analyzer::new(FFT_LEN, Default::default());
+        let mut tracker = CentroidTracker::new(analyzer, SAMPLING_RATE, 0.5).unwrap();
+
+        let low = vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        let high = vec![0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0];
+
+        tracker.push(&low).unwrap();
+
+        let snapshot = tracker.snapshot();
+        let expected = tracker.push(&high).unwrap();
+
+        tracker.push(&high).unwrap();
+        tracker.restore(snapshot);
+        assert_eq!(expected, tracker.push(&high).unwrap());
+    }
+
+    #[test]
+    fn test_pitch_tracker() {
+        const FFT_LEN: usize = 64;
+
+        // Bins are 1 Hz apart.
+        let analyzer = Analyzer::new(FFT_LEN, Default::default());
+        let mut tracker = PitchTracker::new(FFT_LEN, 5, 0.5, 0.5).unwrap();
+
+        let peak_at = |bin: usize| {
+            let mut spectrum = vec![0.0; FFT_LEN];
+            spectrum[bin] = 1.0;
+            spectrum
+        };
+
+        let tone = peak_at(10);
+        let octave_jump = peak_at(20);
+        let noise = vec![1.0; FFT_LEN];
+
+        assert_eq!(None, tracker.push(&analyzer, &noise).unwrap());
+
+        // Occasional confident outliers are rejected by the median, and unconfident frames are skipped.
+        for i in 0..40 {
+            let spectrum = match i % 7 {
+                3 => &octave_jump,
+                5 => &noise,
+                _ => &tone,
+            };
+
+            assert_eq!(Some(10.0), tracker.push(&analyzer, spectrum).unwrap());
+        }
+
+        // A lasting change does get through.
+        for _ in 0..20 { tracker.push(&analyzer, &octave_jump).unwrap(); }
+        assert_approx_eq!(20.0, tracker.push(&analyzer, &octave_jump).unwrap().unwrap(), 1e-3);
+
+        assert_eq!(Err(Error::WindowLength), PitchTracker::new(FFT_LEN, 0, 0.5, 0.5).map(|_| ()));
+    }
+
+    #[test]
+    fn test_band_trigger() {
+        const FFT_LEN: usize = 64;
+
+        // Bins are 1 Hz apart, so the band covers bins 4 through 7.
+        let analyzer = Analyzer::new(FFT_LEN, Default::default());
+        let mut trigger = BandTrigger::new(&analyzer, FFT_LEN, 4.0, 8.0, 1.0, 0.5).unwrap();
+
+        let band_energy = |energy: SignalStrength| {
+            let mut spectrum = vec![0.0; FFT_LEN];
+            spectrum[5] = energy;
+
+            // Energy outside of the band is ignored.
+            spectrum[8] = 100.0;
+            spectrum[3] = 100.0;
+            spectrum
+        };
+
+        // Jitter around the threshold does not retrigger, until the energy drops below the release level.
+        let levels = [0.0, 0.5, 1.5, 0.9, 1.2, 2.0, 0.6, 1.1, 0.4, 0.3, 1.5, 1.5, 0.0];
+        let produced: Vec<_> = levels.iter().map(|&e| trigger.push(&band_energy(e)).unwrap()).collect();
+
+        let expected = [false, false, true, false, false, false, false, false, false, false, true, false, false];
+        assert_eq!(expected.to_vec(), produced);
+
+        assert_eq!(Err(Error::SpectrumLength(FFT_LEN, 4)), trigger.push(&[0.0; 4]));
+        assert_eq!(Err(Error::CutoffOrder), BandTrigger::new(&analyzer, FFT_LEN, 8.0, 4.0, 1.0, 0.5).map(|_| ()));
+        assert_eq!(Err(Error::Hysteresis), BandTrigger::new(&analyzer, FFT_LEN, 4.0, 8.0, 1.0, 1.0).map(|_| ()));
+        assert_eq!(Err(Error::EmptyBand), BandTrigger::new(&analyzer, FFT_LEN, 4.2, 4.8, 1.0, 0.5).map(|_| ()));
+        assert_eq!(Err(Error::EmptyBand), BandTrigger::new(&analyzer, FFT_LEN, 40.0, 50.0, 1.0, 0.5).map(|_| ()));
+    }
+
+    #[test]
+    fn test_motion_meter() {
+        let mut meter = MotionMeter::new();
+
+        let frame = vec![1.0, 0.5, 0.25, 0.0];
+
+        // A static spectrum does not move.
+        for _ in 0..4 {
+            assert_eq!(0.0, meter.push(&frame));
+        }
+
+        assert_eq!(0.25, meter.push(&[1.0, 1.5, 0.25, 0.0]));
+        assert_eq!(0.75, meter.push(&[0.0, 0.0, 0.75, 0.0]));
+
+        // A change in the number of buckets starts over.
+        assert_eq!(0.0, meter.push(&[1.0, 1.0]));
+    }
+
+    #[test]
+    fn test_transient_detector() {
+        let mut detector = TransientDetector::new(0.5, 60.0, 2.0).unwrap();
+
+        let steady = vec![0.25, 0.5, 0.25];
+        let burst = vec![2.5, 5.0, 2.5];
+
+        let mut flags = Vec::new();
+        for _ in 0..60 { flags.push(detector.push(&steady)); }
+        for _ in 0..5 { flags.push(detector.push(&burst)); }
+        for _ in 0..60 { flags.push(detector.push(&steady)); }
+
+        // Only the start of the burst is flagged.
+        let produced: Vec<_> = flags.iter().enumerate().filter(|(_, &f)| f).map(|(i, _)| i).collect();
+        assert_eq!(vec![60], produced);
+
+        assert_eq!(Err(Error::TimeConstant), TransientDetector::new(0.0, 60.0, 2.0).map(|_| ()));
+        assert_eq!(Err(Error::FrameRate), TransientDetector::new(0.5, 0.0, 2.0).map(|_| ()));
+        assert_eq!(Err(Error::TriggerFactor), TransientDetector::new(0.5, 60.0, 0.0).map(|_| ()));
+    }
+
+    #[test]
+    fn test_band_history() {
+        let mut history = BandHistory::new(1, 8).unwrap();
+
+        // Rises for six frames, then falls for six.
+        let values = vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
+        for &v in &values {
+            history.push(&[-1.0, v, -1.0]).unwrap();
+        }
+
+        assert_eq!(8, history.iter().count());
+        assert_eq!(vec![&4.0, &5.0, &6.0, &5.0], history.iter().take(4).collect::<Vec<_>>());
+
+        let produced: Vec<char> = history.render_sparkline(8).chars().collect();
+        assert_eq!(8, produced.len());
+        assert_eq!('█', produced[2]);
+        assert!(produced[..3].windows(2).all(|w| w[0] < w[1]));
+        assert!(produced[2..].windows(2).all(|w| w[0] > w[1]));
+
+        // Narrower sparklines only show the most recent values.
+        assert_eq!("█▆▃▁", history.render_sparkline(4));
+        assert_eq!("", history.render_sparkline(0));
+
+        let mut flat = BandHistory::new(0, 4).unwrap();
+        for _ in 0..3 { flat.push(&[0.5]).unwrap(); }
+        assert_eq!("▁▁▁", flat.render_sparkline(8));
+
+        assert_eq!(Err(Error::BucketIndex(1, 1)), history.push(&[0.0]));
+        assert_eq!(Err(Error::WindowLength), BandHistory::new(0, 0).map(|_| ()));
+    }
+
+    #[test]
+    fn test_flux_history() {
+        const PERIOD: usize = 8;
+
+        let mut history = FluxHistory::new(32).unwrap();
+        assert_eq!(None, history.flux_at_lag(1));
+
+        // A spectrum whose energy moves between two bins with a period of eight frames.
+        for i in 0..40 {
+            let phase = (i % PERIOD) as f32 / PERIOD as f32 * 2.0 * std::f32::consts::PI;
+            history.push(&[1.0 + phase.cos(), 1.0 - phase.cos(), 0.5]);
+        }
+
+        assert_eq!(32, history.len());
+
+        let at_period = history.flux_at_lag(PERIOD).unwrap();
+        let at_half_period = history.flux_at_lag(PERIOD / 2).unwrap();
+
+        assert_approx_eq!(0.0, at_period);
+        assert_approx_eq!(0.0, history.flux_at_lag(2 * PERIOD).unwrap());
+        assert!(at_half_period > 1.0);
+
+        assert_eq!(None, history.flux_at_lag(0));
+        assert!(history.flux_at_lag(31).is_some());
+        assert_eq!(None, history.flux_at_lag(32));
+
+        history.push(&[1.0]);
+        assert_eq!(None, history.flux_at_lag(1));
+
+        assert_eq!(Err(Error::WindowLength), FluxHistory::new(0).map(|_| ()));
+    }
+}
@@ -12,17 +12,32 @@ pub enum WaveFunction {
     Triangle,
     Sawtooth,
     SineMag,
+    WhiteNoise,
 }
 
 impl WaveFunction {
     pub fn val(&self, sample_index: usize, samples_per_period: usize, frequency: Frequency) -> Sample {
         let f_x = sample_index as f32 * frequency / samples_per_period as f32;
-        AMPLITUDE * match self {
+        AMPLITUDE * self.shape(sample_index, f_x)
+    }
+
+    // Value of the unit-amplitude waveform at `f_x` periods in.
+    // Noise ignores the position, and is instead derived from the sample index so that it is repeatable.
+    fn shape(&self, sample_index: usize, f_x: f32) -> Sample {
+        match self {
             &WaveFunction::Sine => (2.0 * PI * f_x).sin(),
             &WaveFunction::Square => (-1.0f32).powf((2.0 * f_x).floor()),
             &WaveFunction::Triangle => 1.0 - 4.0 * (0.5 - (f_x + 0.25).fract()).abs(),
             &WaveFunction::Sawtooth => 2.0 * f_x.fract() - 1.0,
             &WaveFunction::SineMag => 2.0 * (PI * f_x).sin().abs() - 1.0,
+            &WaveFunction::WhiteNoise => {
+                // Hashes the index with a fixed seed, using the MurmurHash3 finalizer.
+                let mut state = (sample_index as u32) ^ 0x1234_5678;
+                state = (state ^ (state >> 16)).wrapping_mul(0x85EB_CA6B);
+                state = (state ^ (state >> 13)).wrapping_mul(0xC2B2_AE35);
+                state ^= state >> 16;
+                2.0 * (state as f32 / u32::MAX as f32) - 1.0
+            },
         }
     }
 }
@@ -32,6 +47,8 @@ pub struct WaveGen {
     samples_per_period: usize,
     sample_index: usize,
     frequency: Frequency,
+    amplitude: f32,
+    phase: f32,
 }
 
 impl WaveGen {
@@ -41,16 +58,81 @@ impl WaveGen {
             samples_per_period,
             sample_index: 0,
             frequency,
+            amplitude: AMPLITUDE,
+            phase: 0.0,
         }
     }
+
+    /// Sets the peak amplitude of the wave, which defaults to 0.25.
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// Sets the starting phase of the wave in radians.
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self
+    }
 }
 
 impl Iterator for WaveGen {
     type Item = Sample;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let v = self.function.val(self.sample_index, self.samples_per_period, self.frequency);
+        let f_x = self.sample_index as f32 * self.frequency / self.samples_per_period as f32 + self.phase / (2.0 * PI);
+        let v = self.amplitude * self.function.shape(self.sample_index, f_x);
         self.sample_index = (self.sample_index + 1) % self.samples_per_period;
         Some(v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES_PER_PERIOD: usize = 44100;
+
+    #[test]
+    fn test_wave_gen() {
+        let kinds = [
+            WaveFunction::Sine,
+            WaveFunction::Square,
+            WaveFunction::Triangle,
+            WaveFunction::Sawtooth,
+            WaveFunction::SineMag,
+            WaveFunction::WhiteNoise,
+        ];
+
+        for kind in &kinds {
+            let produced: Vec<_> = WaveGen::new(*kind, SAMPLES_PER_PERIOD, 440.0).take(1000).collect();
+            let expected: Vec<_> = (0..1000).map(|i| kind.val(i, SAMPLES_PER_PERIOD, 440.0)).collect();
+            assert_eq!(expected, produced);
+
+            // Every waveform stays within its amplitude.
+            let louder: Vec<_> = WaveGen::new(*kind, SAMPLES_PER_PERIOD, 440.0).with_amplitude(1.0).take(1000).collect();
+            assert!(louder.iter().all(|v| v.abs() <= 1.0));
+            assert!(louder.iter().any(|v| v.abs() > 0.9));
+        }
+
+        // A quarter period of phase turns a sine into a cosine.
+        let produced: Vec<_> = WaveGen::new(WaveFunction::Sine, SAMPLES_PER_PERIOD, 440.0).with_amplitude(1.0).with_phase(PI / 2.0).take(100).collect();
+        for (i, p) in produced.into_iter().enumerate() {
+            assert_approx_eq!((2.0 * PI * 440.0 * i as f32 / SAMPLES_PER_PERIOD as f32).cos(), p, 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_white_noise() {
+        let first: Vec<_> = WaveGen::new(WaveFunction::WhiteNoise, SAMPLES_PER_PERIOD, 440.0).take(4096).collect();
+        let second: Vec<_> = WaveGen::new(WaveFunction::WhiteNoise, SAMPLES_PER_PERIOD, 440.0).take(4096).collect();
+
+        // Repeatable, roughly zero mean, and with the variance of a uniform distribution.
+        assert_eq!(first, second);
+
+        let mean = first.iter().sum::<f32>() / first.len() as f32;
+        let variance = first.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / first.len() as f32;
+        assert!(mean.abs() < 0.01);
+        assert_approx_eq!(AMPLITUDE.powi(2) / 3.0, variance, 0.002);
+    }
+}
@@ -0,0 +1,171 @@
+//! Frequency weighting curves.
+
+use crate::types::Frequency;
+
+// ISO 226:2003 equal-loudness contour parameters.
+const ISO_226_FREQUENCIES: [Frequency; 29] = [
+    20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0, 500.0,
+    630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0, 4000.0, 5000.0, 6300.0, 8000.0, 10000.0, 12500.0,
+];
+const ISO_226_EXPONENTS: [f32; 29] = [
+    0.532, 0.506, 0.480, 0.455, 0.432, 0.409, 0.387, 0.367, 0.349, 0.330, 0.315, 0.301, 0.288, 0.276, 0.267,
+    0.259, 0.253, 0.250, 0.246, 0.244, 0.243, 0.243, 0.243, 0.242, 0.242, 0.245, 0.254, 0.271, 0.301,
+];
+const ISO_226_TRANSFER_MAGNITUDES: [f32; 29] = [
+    -31.6, -27.2, -23.0, -19.1, -15.9, -13.0, -10.3, -8.1, -6.2, -4.5, -3.1, -2.0, -1.1, -0.4, 0.0,
+    0.3, 0.5, 0.0, -2.7, -4.1, -1.0, 1.7, 2.5, 1.2, -2.1, -7.1, -11.2, -10.7, -3.1,
+];
+const ISO_226_THRESHOLDS: [f32; 29] = [
+    78.5, 68.7, 59.5, 51.1, 44.0, 37.5, 31.5, 26.5, 22.1, 17.9, 14.4, 11.4, 8.6, 6.2, 4.4,
+    3.0, 2.2, 2.4, 3.5, 1.7, -1.3, -4.2, -6.0, -5.4, -1.5, 6.0, 12.6, 13.9, 12.3,
+];
+
+// Sound pressure level in decibels at the ISO 226 table entry with the given index.
+fn iso_226_level(index: usize, phon: f32) -> f32 {
+    let af = ISO_226_EXPONENTS[index];
+    let lu = ISO_226_TRANSFER_MAGNITUDES[index];
+    let tf = ISO_226_THRESHOLDS[index];
+
+    let a = 4.47e-3 * (10.0f32.powf(0.025 * phon) - 1.15) + (0.4 * 10.0f32.powf((tf + lu) / 10.0 - 9.0)).powf(af);
+
+    10.0 / af * a.log10() - lu + 94.0
+}
+
+/// Calculates the sound pressure level in decibels that is perceived as loud as a 1 kHz tone at `phon`,
+/// following the ISO 226:2003 equal-loudness contours.
+/// Between table frequencies the level is interpolated on a logarithmic frequency axis,
+/// and outside of 20 Hz to 12.5 kHz the nearest table entry is used.
+pub fn equal_loudness_level(frequency: Frequency, phon: f32) -> f32 {
+    let last = ISO_226_FREQUENCIES.len() - 1;
+
+    if !(frequency > ISO_226_FREQUENCIES[0]) { return iso_226_level(0, phon) }
+    if !(frequency < ISO_226_FREQUENCIES[last]) { return iso_226_level(last, phon) }
+
+    let upper = ISO_226_FREQUENCIES.iter().position(|f| *f >= frequency).unwrap();
+    let lower = upper - 1;
+
+    let t = (frequency / ISO_226_FREQUENCIES[lower]).ln() / (ISO_226_FREQUENCIES[upper] / ISO_226_FREQUENCIES[lower]).ln();
+
+    (1.0 - t) * iso_226_level(lower, phon) + t * iso_226_level(upper, phon)
+}
+
+/// Calculates the power gain that compensates for the ear's sensitivity at a frequency, relative to 1 kHz.
+pub fn equal_loudness_gain(frequency: Frequency, phon: f32) -> f32 {
+    let relative_db = equal_loudness_level(1000.0, phon) - equal_loudness_level(frequency, phon);
+    10.0f32.powf(relative_db / 10.0)
+}
+
+/// Calculates the IEC 61672 A-weighting at a frequency in decibels, which is zero at 1 kHz.
+/// The zero frequency is infinitely attenuated.
+pub fn a_weighting_db(frequency: Frequency) -> f32 {
+    let f2 = frequency.powi(2);
+
+    let numerator = 12194.0f32.powi(2) * f2.powi(2);
+    let denominator = (f2 + 20.6f32.powi(2)) * ((f2 + 107.7f32.powi(2)) * (f2 + 737.9f32.powi(2))).sqrt() * (f2 + 12194.0f32.powi(2));
+
+    20.0 * (numerator / denominator).log10() + 2.0
+}
+
+/// Calculates the IEC 61672 C-weighting at a frequency in decibels, which is zero at 1 kHz.
+/// The zero frequency is infinitely attenuated.
+pub fn c_weighting_db(frequency: Frequency) -> f32 {
+    let f2 = frequency.powi(2);
+
+    let numerator = 12194.0f32.powi(2) * f2;
+    let denominator = (f2 + 20.6f32.powi(2)) * (f2 + 12194.0f32.powi(2));
+
+    20.0 * (numerator / denominator).log10() + 0.06
+}
+
+/// A standard frequency weighting curve for sound level measurement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weighting {
+    /// Follows the ear's sensitivity at low levels, strongly attenuating bass.
+    A,
+    /// Nearly flat, only rolling off at the extremes, for peak and high level measurement.
+    C,
+    /// No weighting at all.
+    Z,
+}
+
+impl Weighting {
+    /// Calculates the gain of this weighting at a frequency in decibels.
+    pub fn gain_db(self, frequency: Frequency) -> f32 {
+        match self {
+            Weighting::A => a_weighting_db(frequency),
+            Weighting::C => c_weighting_db(frequency),
+            Weighting::Z => 0.0,
+        }
+    }
+
+    /// Calculates the gain of this weighting at a frequency as a power ratio.
+    pub fn power_gain(self, frequency: Frequency) -> f32 {
+        10.0f32.powf(self.gain_db(frequency) / 10.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equal_loudness_level() {
+        // At 1 kHz, the level in decibels is the loudness in phons.
+        for phon in [20.0, 40.0, 60.0, 80.0] {
+            assert_approx_eq!(phon, equal_loudness_level(1000.0, phon), 0.1);
+        }
+
+        // Published 40 phon contour values.
+        assert_approx_eq!(99.85, equal_loudness_level(20.0, 40.0), 0.1);
+        assert_approx_eq!(60.59, equal_loudness_level(125.0, 40.0), 0.1);
+        assert_approx_eq!(36.65, equal_loudness_level(4000.0, 40.0), 0.1);
+
+        // Outside of the table, the nearest entry is used.
+        assert_eq!(equal_loudness_level(20.0, 40.0), equal_loudness_level(10.0, 40.0));
+        assert_eq!(equal_loudness_level(12500.0, 40.0), equal_loudness_level(20000.0, 40.0));
+    }
+
+    #[test]
+    fn test_equal_loudness_gain() {
+        assert_approx_eq!(1.0, equal_loudness_gain(1000.0, 60.0), 0.01);
+        assert!(equal_loudness_gain(50.0, 60.0) < 1.0);
+        assert!(equal_loudness_gain(12500.0, 60.0) < 1.0);
+        assert!(equal_loudness_gain(3150.0, 60.0) > 1.0);
+    }
+
+    #[test]
+    fn test_a_weighting_db() {
+        // Published IEC 61672 values.
+        assert_approx_eq!(0.0, a_weighting_db(1000.0), 0.01);
+        assert_approx_eq!(-50.5, a_weighting_db(20.0), 0.2);
+        assert_approx_eq!(-19.1, a_weighting_db(100.0), 0.1);
+        assert_approx_eq!(1.0, a_weighting_db(4000.0), 0.1);
+        assert_approx_eq!(-9.3, a_weighting_db(20000.0), 0.1);
+
+        assert_eq!(f32::NEG_INFINITY, a_weighting_db(0.0));
+    }
+
+    #[test]
+    fn test_c_weighting_db() {
+        // Published IEC 61672 values.
+        assert_approx_eq!(0.0, c_weighting_db(1000.0), 0.01);
+        assert_approx_eq!(-6.2, c_weighting_db(20.0), 0.1);
+        assert_approx_eq!(-0.3, c_weighting_db(100.0), 0.1);
+        assert_approx_eq!(-0.8, c_weighting_db(4000.0), 0.1);
+        assert_approx_eq!(-11.2, c_weighting_db(20000.0), 0.1);
+
+        assert_eq!(f32::NEG_INFINITY, c_weighting_db(0.0));
+    }
+
+    #[test]
+    fn test_weighting() {
+        for frequency in [20.0, 100.0, 1000.0, 4000.0] {
+            assert_eq!(a_weighting_db(frequency), Weighting::A.gain_db(frequency));
+            assert_eq!(c_weighting_db(frequency), Weighting::C.gain_db(frequency));
+            assert_eq!(1.0, Weighting::Z.power_gain(frequency));
+        }
+
+        assert_approx_eq!(0.1, Weighting::A.power_gain(100.0) * 10.0f32.powf(0.91), 1e-2);
+        assert_eq!(0.0, Weighting::A.power_gain(0.0));
+    }
+}
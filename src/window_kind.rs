@@ -1,26 +1,83 @@
 use std::f64::consts::PI;
 
+use crate::Error;
+
 #[derive(Clone, Copy, Debug)]
 pub enum WindowKind {
     Blackman,
     Hamming,
     Hanning,
+    /// The square of `Hanning`, which satisfies COLA at 75% overlap when used for both analysis and synthesis.
+    HanningSquared,
     Rectangular,
     Sine,
     Triangular,
+    /// Flat in the center, with smooth tapers covering a fraction `epsilon` of the window at each end.
+    /// Values of `epsilon` are clamped to between zero and one half, where zero is the same as `Rectangular`.
+    PlanckTaper { epsilon: f32 },
 }
 
 impl WindowKind {
     pub fn generate(&self, len: usize) -> Vec<f64> {
+        // The symmetric windows are defined in terms of `len - 1`, which would divide by zero for a single sample.
+        if len <= 1 { return vec![1.0; len] }
+
         match self {
             &WindowKind::Blackman => apodize::blackman_iter(len).collect(),
             &WindowKind::Hamming => apodize::hamming_iter(len).collect(),
             &WindowKind::Hanning => apodize::hanning_iter(len).collect(),
+            &WindowKind::HanningSquared => apodize::hanning_iter(len).map(|w| w * w).collect(),
             &WindowKind::Rectangular => vec![1.0; len],
             &WindowKind::Sine => (0..len).map(|i| (i as f64 / (len - 1) as f64 * PI).sin()).collect(),
             &WindowKind::Triangular => apodize::triangular_iter(len).collect(),
+            &WindowKind::PlanckTaper { epsilon } => {
+                let last = (len - 1) as f64;
+                let taper = epsilon.clamp(0.0, 0.5) as f64 * last;
+
+                (0..len)
+                .map(|i| {
+                    // Distance from the nearest end, as the window is symmetric.
+                    let k = (i as f64).min(last - i as f64);
+
+                    if k >= taper { 1.0 }
+                    else if k <= 0.0 { 0.0 }
+                    else { 1.0 / (1.0 + (taper / k - taper / (taper - k)).exp()) }
+                })
+                .collect()
+            },
         }
     }
+
+    /// Generates a window of a length whose coefficients are linearly interpolated between two kinds of window.
+    /// A `mix` of zero gives `a`, and a `mix` of one gives `b`, such as to trade off between `Hanning` and `Blackman`.
+    pub fn blend(a: &WindowKind, b: &WindowKind, mix: f32, len: usize) -> Result<Vec<f64>, Error> {
+        if !(0.0..=1.0).contains(&mix) { Err(Error::BlendFactor)? }
+
+        let mix = mix as f64;
+        let blended = a.generate(len).into_iter().zip(b.generate(len)).map(|(wa, wb)| (1.0 - mix) * wa + mix * wb).collect();
+
+        Ok(blended)
+    }
+
+    /// Calculates the equivalent noise bandwidth of this window at a length, in bins.
+    /// This is how much wider than a single bin the window makes each bin's passband, such as 1.5 for `Hanning`.
+    pub fn enbw(&self, len: usize) -> f64 {
+        let window = self.generate(len);
+
+        let sum: f64 = window.iter().sum();
+        let sum_squares: f64 = window.iter().map(|w| w * w).sum();
+
+        if sum > 0.0 { len as f64 * sum_squares / sum.powi(2) }
+        else { 1.0 }
+    }
+
+    /// Calculates the coherent gain of this window at a length, which is the mean of its coefficients.
+    /// Windowing scales the amplitude of a sinusoid by this factor, and its power by the square.
+    pub fn coherent_gain(&self, len: usize) -> f64 {
+        if len == 0 { return 1.0 }
+
+        self.generate(len).iter().sum::<f64>() / len as f64
+    }
 }
 
 impl Default for WindowKind {
@@ -54,4 +111,101 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_generate_short() {
+        let kinds = [
+            WindowKind::Blackman,
+            WindowKind::Hamming,
+            WindowKind::Hanning,
+            WindowKind::HanningSquared,
+            WindowKind::Rectangular,
+            WindowKind::Sine,
+            WindowKind::Triangular,
+            WindowKind::PlanckTaper { epsilon: 0.1 },
+        ];
+
+        for kind in &kinds {
+            assert_eq!(Vec::<f64>::new(), kind.generate(0));
+            assert_eq!(vec![1.0], kind.generate(1));
+        }
+    }
+
+    #[test]
+    fn test_blend() {
+        const LEN: usize = 64;
+
+        let (a, b) = (WindowKind::Hanning, WindowKind::Blackman);
+
+        assert_eq!(a.generate(LEN), WindowKind::blend(&a, &b, 0.0, LEN).unwrap());
+        assert_eq!(b.generate(LEN), WindowKind::blend(&a, &b, 1.0, LEN).unwrap());
+
+        let produced = WindowKind::blend(&a, &b, 0.25, LEN).unwrap();
+        for ((wa, wb), p) in a.generate(LEN).into_iter().zip(b.generate(LEN)).zip(produced) {
+            assert_approx_eq!(0.75 * wa + 0.25 * wb, p);
+        }
+
+        assert_eq!(Err(Error::BlendFactor), WindowKind::blend(&a, &b, 1.5, LEN));
+    }
+
+    #[test]
+    fn test_coherent_gain() {
+        const LEN: usize = 4096;
+
+        assert_approx_eq!(1.0, WindowKind::Rectangular.coherent_gain(LEN));
+        assert_approx_eq!(0.5, WindowKind::Hanning.coherent_gain(LEN), 1e-3);
+        assert_approx_eq!(0.54, WindowKind::Hamming.coherent_gain(LEN), 1e-3);
+        assert_approx_eq!(0.35875, WindowKind::Blackman.coherent_gain(LEN), 1e-3);
+
+        assert_eq!(1.0, WindowKind::Hanning.coherent_gain(0));
+    }
+
+    #[test]
+    fn test_enbw() {
+        const LEN: usize = 4096;
+
+        assert_approx_eq!(1.0, WindowKind::Rectangular.enbw(LEN));
+        assert_approx_eq!(1.5, WindowKind::Hanning.enbw(LEN), 1e-3);
+        assert_approx_eq!(1.363, WindowKind::Hamming.enbw(LEN), 1e-3);
+
+        assert_eq!(1.0, WindowKind::Hanning.enbw(0));
+    }
+
+    #[test]
+    fn test_hanning_squared() {
+        const LEN: usize = 64;
+
+        let hanning = WindowKind::Hanning.generate(LEN);
+        let produced = WindowKind::HanningSquared.generate(LEN);
+
+        for (h, p) in hanning.into_iter().zip(produced) {
+            assert_approx_eq!(h * h, p);
+        }
+    }
+
+    #[test]
+    fn test_planck_taper() {
+        const LEN: usize = 1001;
+
+        let produced = WindowKind::PlanckTaper { epsilon: 0.1 }.generate(LEN);
+
+        // Zero at the very ends, and flat outside of the first and last 100 samples.
+        assert_eq!(0.0, produced[0]);
+        assert_eq!(0.0, produced[LEN - 1]);
+        assert!(produced[100..=900].iter().all(|&w| w == 1.0));
+
+        // Rises smoothly through the taper, meeting the flat region without a jump.
+        for (i, pair) in produced.windows(2).enumerate() {
+            assert!((pair[1] - pair[0]).abs() < 0.05);
+            if i < 100 { assert!(pair[1] >= pair[0]) }
+        }
+        assert_approx_eq!(0.5, produced[50], 1e-6);
+        assert!(produced[99] > 0.999);
+
+        for (e, p) in produced.iter().zip(produced.iter().rev()) {
+            assert_approx_eq!(e, p, 1e-9);
+        }
+
+        assert_eq!(WindowKind::Rectangular.generate(16), WindowKind::PlanckTaper { epsilon: 0.0 }.generate(16));
+    }
 }